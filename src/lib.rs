@@ -1,34 +1,812 @@
-use chrono::offset::Utc;
-use regex::Regex;
-use std::{collections::HashMap, fmt::Debug};
+use chrono::{offset::Utc, DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use futures_util::{stream, StreamExt};
 use reqwest::{header, Client};
 
+use endpoint::Endpoint;
+
 pub(crate) mod utils {
 
-    use serde::de::{self, Deserialize, Deserializer};
+    use chrono::{offset::Utc, DateTime, NaiveDate, NaiveDateTime};
+    use regex::Regex;
+    use serde::de::{
+        self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor,
+    };
+    use serde_json::Value;
+    use std::cell::Cell;
     use std::fmt::Display;
     use std::str::FromStr;
+    use std::time::Duration;
+
+    use crate::ParseMode;
+
+    thread_local! {
+        static PARSE_MODE: Cell<ParseMode> = const { Cell::new(ParseMode::Standard) };
+    }
+
+    /// Run `f`, usually a serde call, with the deserializers below following
+    /// `mode`. Deserialization is synchronous so a thread local is enough.
+    pub fn with_parse_mode<R>(mode: ParseMode, f: impl FnOnce() -> R) -> R {
+        let previous = PARSE_MODE.with(|m| m.replace(mode));
+        let ret = f();
+        PARSE_MODE.with(|m| m.set(previous));
+        ret
+    }
+
+    /// In lenient mode a value that can not be parsed is replaced by
+    /// `fallback` with a warning, in strict mode the error is kept.
+    fn recover<T, E: Display>(result: Result<T, E>, fallback: T) -> Result<T, E> {
+        match result {
+            Err(e) if PARSE_MODE.with(Cell::get) == ParseMode::Lenient => {
+                log::warn!("ignoring invalid value: {}", e);
+                Ok(fallback)
+            }
+            other => other,
+        }
+    }
+
+    /// Deserializer over a `Value` that fails on the fields the target type
+    /// doesn't map, used for `ParseMode::Strict`
+    pub struct Strict {
+        value: Value,
+        field: Option<String>,
+    }
+
+    impl Strict {
+        pub fn new(value: Value) -> Self {
+            Self { value, field: None }
+        }
+    }
+
+    impl<'de> Deserializer<'de> for Strict {
+        type Error = serde_json::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.value {
+                Value::Object(map) => visitor.visit_map(StrictMap {
+                    entries: map.into_iter(),
+                    value: None,
+                }),
+                Value::Array(items) => visitor.visit_seq(StrictSeq(items.into_iter())),
+                other => other.deserialize_any(visitor),
+            }
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.value {
+                Value::Null => visitor.visit_none(),
+                _ => visitor.visit_some(self),
+            }
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.value.deserialize_enum(name, variants, visitor)
+        }
+
+        // Serde derive skips the value of an unknown field this way
+        fn deserialize_ignored_any<V: Visitor<'de>>(
+            self,
+            visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            match self.field {
+                Some(field) => Err(de::Error::custom(format!("unknown field `{}`", field))),
+                None => visitor.visit_unit(),
+            }
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        }
+    }
+
+    struct StrictMap {
+        entries: serde_json::map::IntoIter,
+        value: Option<(String, Value)>,
+    }
+
+    impl<'de> MapAccess<'de> for StrictMap {
+        type Error = serde_json::Error;
+
+        fn next_key_seed<K: DeserializeSeed<'de>>(
+            &mut self,
+            seed: K,
+        ) -> Result<Option<K::Value>, Self::Error> {
+            let Some((key, value)) = self.entries.next() else {
+                return Ok(None);
+            };
+
+            let ret = seed.deserialize(de::value::StringDeserializer::new(key.clone()))?;
+            self.value = Some((key, value));
+            Ok(Some(ret))
+        }
+
+        fn next_value_seed<V: DeserializeSeed<'de>>(
+            &mut self,
+            seed: V,
+        ) -> Result<V::Value, Self::Error> {
+            let (field, value) = self
+                .value
+                .take()
+                .ok_or_else(|| de::Error::custom("value without a key"))?;
+            seed.deserialize(Strict {
+                value,
+                field: Some(field),
+            })
+        }
+    }
+
+    struct StrictSeq(std::vec::IntoIter<Value>);
+
+    impl<'de> SeqAccess<'de> for StrictSeq {
+        type Error = serde_json::Error;
+
+        fn next_element_seed<T: DeserializeSeed<'de>>(
+            &mut self,
+            seed: T,
+        ) -> Result<Option<T::Value>, Self::Error> {
+            self.0
+                .next()
+                .map(|value| seed.deserialize(Strict { value, field: None }))
+                .transpose()
+        }
+    }
+
+    /// Depending on endpoint and firmware values are sent either quoted or
+    /// as plain JSON numbers/booleans, accept all of them. Anything else,
+    /// `null` included, is an error so that lenient mode can recover from it.
+    fn scalar(value: Value) -> Result<String, String> {
+        match value {
+            Value::String(s) => Ok(s),
+            Value::Number(n) => Ok(n.to_string()),
+            Value::Bool(b) => Ok(b.to_string()),
+            other => Err(format!("expected a string or a number, found {}", other)),
+        }
+    }
+
+    fn parse_scalar<T>(value: Value) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let s = scalar(value)?;
+        T::from_str(&s).map_err(|e| e.to_string())
+    }
 
     pub fn from_str<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        recover(parse_scalar(value), T::default()).map_err(de::Error::custom)
+    }
+
+    /// Percentage, out of range values are clamped to 100 in lenient mode
+    pub fn percent<'de, D>(deserializer: D) -> Result<u32, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: u32 = from_str(deserializer)?;
+        if value <= 100 {
+            return Ok(value);
+        }
+
+        recover(Err(format!("percentage {} out of range", value)), 100).map_err(de::Error::custom)
+    }
+
+    pub fn option_from_str<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::String(s) if s.is_empty() => Ok(None),
+            value => recover(parse_scalar(value).map(Some), None).map_err(de::Error::custom),
+        }
+    }
+
+    /// Diagnostic only some firmwares report, and not always well formed
+    /// (`"--"`, `-1`, a boolean): a value that can not be parsed is `None`
+    /// with a warning so it doesn't fail the whole status, except in strict
+    /// mode.
+    pub fn option_diagnostic<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
     where
         T: FromStr,
         T::Err: Display,
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        T::from_str(&s).map_err(de::Error::custom)
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::String(s) if s.is_empty() => Ok(None),
+            value => match parse_scalar(value) {
+                Ok(parsed) => Ok(Some(parsed)),
+                Err(e) if PARSE_MODE.with(Cell::get) == ParseMode::Strict => {
+                    Err(de::Error::custom(e))
+                }
+                Err(e) => {
+                    log::warn!("ignoring invalid diagnostic value: {}", e);
+                    Ok(None)
+                }
+            },
+        }
+    }
+
+    // Formats used by the portal, every date sent or parsed goes through them
+    pub const DATE_FORMAT: &str = "%Y-%m-%d";
+    pub const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+    pub fn format_date(date: NaiveDate) -> String {
+        date.format(DATE_FORMAT).to_string()
+    }
+
+    pub fn datetime<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let time = scalar(Value::deserialize(deserializer)?).and_then(|s| {
+            NaiveDateTime::parse_from_str(&s, DATETIME_FORMAT).map_err(|e| e.to_string())
+        });
+        recover(time, NaiveDateTime::default()).map_err(de::Error::custom)
+    }
+
+    pub fn option_datetime<'de, D>(deserializer: D) -> Result<Option<NaiveDateTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::String(s) if s.is_empty() => Ok(None),
+            value => {
+                let time = scalar(value).and_then(|s| {
+                    NaiveDateTime::parse_from_str(&s, DATETIME_FORMAT).map_err(|e| e.to_string())
+                });
+                recover(time.map(Some), None).map_err(de::Error::custom)
+            }
+        }
+    }
+
+    /// Chart series, where missing samples are sent as `null` or `""`
+    pub fn series<'de, D>(deserializer: D) -> Result<Vec<Option<f32>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|v| match v {
+                Value::Null => Ok(None),
+                Value::String(s) if s.is_empty() => Ok(None),
+                value => recover(parse_scalar(value).map(Some), None).map_err(de::Error::custom),
+            })
+            .collect()
+    }
+
+    /// `Retry-After` is either a number of seconds or an HTTP date
+    pub fn parse_retry_after(value: &str) -> Option<Duration> {
+        let value = value.trim();
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+
+        let at = DateTime::parse_from_rfc2822(value).ok()?;
+        let wait = at.with_timezone(&Utc) - Utc::now();
+        Some(wait.to_std().unwrap_or(Duration::ZERO))
+    }
+
+    /// Maintenance is announced with an HTML page in place of the JSON,
+    /// either served as 503 or titled as a maintenance notice. Other HTML,
+    /// like the login page of an expired session, is not maintenance even
+    /// when the word shows up somewhere in it.
+    pub fn is_maintenance_page(status: u16, body: &str) -> bool {
+        let body = body.trim_start();
+        if !body.starts_with('<') {
+            return false;
+        }
+        if status == 503 {
+            return true;
+        }
+
+        let lower = body.to_lowercase();
+        let title = lower
+            .split_once("<title>")
+            .and_then(|(_, rest)| rest.split_once("</title>"))
+            .map(|(title, _)| title);
+        matches!(title, Some(title) if title.contains("maintenance") || title.contains("维护"))
+    }
+
+    /// Last date and time written on the maintenance page, that is usually
+    /// when it ends
+    pub fn maintenance_until(body: &str) -> Option<NaiveDateTime> {
+        let re = Regex::new(r"(\d{4}-\d{2}-\d{2}) (\d{2}:\d{2})(:\d{2})?").unwrap();
+        re.captures_iter(body)
+            .filter_map(|caps| {
+                let datetime = format!(
+                    "{} {}{}",
+                    &caps[1],
+                    &caps[2],
+                    caps.get(3).map_or(":00", |m| m.as_str())
+                );
+                NaiveDateTime::parse_from_str(&datetime, DATETIME_FORMAT).ok()
+            })
+            .last()
+    }
+
+    /// Name and value of a `Set-Cookie` header, without its attributes
+    pub fn cookie_pair(set_cookie: &str) -> Option<(&str, &str)> {
+        let pair = set_cookie.split(';').next()?;
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+        (!name.is_empty()).then(|| (name, value.trim()))
+    }
+
+    /// Rename the `(from, to)` keys of an object, when both spellings are
+    /// present the one already under `to` is kept
+    pub fn rename_keys(obj: &mut Value, renames: &[(&str, &str)]) {
+        let Some(map) = obj.as_object_mut() else {
+            return;
+        };
+        for (from, to) in renames {
+            if let Some(value) = map.remove(*from) {
+                map.entry(*to).or_insert(value);
+            }
+        }
+    }
+
+    pub fn snippet(s: &str, max_len: usize) -> &str {
+        match s.char_indices().nth(max_len) {
+            Some((idx, _)) => &s[..idx],
+            None => s,
+        }
+    }
+
+    pub fn flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let flag = scalar(Value::deserialize(deserializer)?).and_then(|s| match s.as_str() {
+            "1" | "true" => Ok(true),
+            "0" | "false" | "" => Ok(false),
+            other => Err(format!("invalid flag {}", other)),
+        });
+        recover(flag, false).map_err(de::Error::custom)
+    }
+}
+
+/// Every portal endpoint the client talks to, so that paths, query and form
+/// parameters are spelled in a single place.
+pub(crate) mod endpoint {
+    use chrono::NaiveDate;
+
+    use crate::utils::format_date;
+    use crate::NotificationSettings;
+
+    pub enum Endpoint<'a> {
+        Login {
+            account: &'a str,
+            password: &'a str,
+        },
+        MixStatus {
+            plant_id: &'a str,
+            mix_sn: &'a str,
+        },
+        MixTotal {
+            plant_id: &'a str,
+            mix_sn: &'a str,
+        },
+        DevicesByPlant {
+            plant_id: &'a str,
+            page: u32,
+        },
+        PlantData {
+            plant_id: &'a str,
+        },
+        PlantExpectEnergy {
+            plant_id: &'a str,
+            date: NaiveDate,
+        },
+        PlantImage {
+            image_name: &'a str,
+        },
+        DatalogInfo {
+            plant_id: &'a str,
+            datalog_sn: &'a str,
+        },
+        MessageList {
+            page: u32,
+        },
+        MessageRead {
+            message_id: u64,
+        },
+        PlantNoticeSet {
+            plant_id: &'a str,
+        },
+        SetPlantNoticeSet {
+            plant_id: &'a str,
+            settings: &'a NotificationSettings,
+        },
+        MixSohHistory {
+            plant_id: &'a str,
+            mix_sn: &'a str,
+            start: NaiveDate,
+            end: NaiveDate,
+        },
+        MixEnergyDayChart {
+            plant_id: &'a str,
+            mix_sn: &'a str,
+            date: NaiveDate,
+        },
+        MixSetParams {
+            mix_sn: &'a str,
+        },
+        /// Write to a device, `params` are sent as `param1`, `param2`, ...
+        TcpSet {
+            action: &'a str,
+            serial: &'a str,
+            setting_type: &'a str,
+            params: &'a [&'a str],
+        },
+        MixHistory {
+            plant_id: &'a str,
+            mix_sn: &'a str,
+            date: NaiveDate,
+            page: u32,
+        },
+        PlantCircuitLoad {
+            plant_id: &'a str,
+        },
+        DeviceInfo {
+            plant_id: &'a str,
+            device_sn: &'a str,
+        },
+        DeviceTypeList,
+        PlantList,
+        PlantFaultLog {
+            plant_id: &'a str,
+            start: NaiveDate,
+            end: NaiveDate,
+            page: u32,
+        },
+    }
+
+    impl Endpoint<'_> {
+        pub fn path(&self) -> String {
+            match self {
+                Self::Login { .. } => "login".to_owned(),
+                Self::MixStatus { .. } => "panel/mix/getMIXStatusData".to_owned(),
+                Self::MixTotal { .. } => "panel/mix/getMIXTotalData".to_owned(),
+                Self::DevicesByPlant { .. } => "panel/getDevicesByPlantList".to_owned(),
+                Self::PlantData { .. } => "panel/getPlantData".to_owned(),
+                Self::PlantExpectEnergy { .. } => "panel/getPlantExpectEnergy".to_owned(),
+                Self::PlantImage { image_name } => format!("upload/plantImg/{}", image_name),
+                Self::DatalogInfo { .. } => "panel/getDatalogInfo".to_owned(),
+                Self::MessageList { .. } => "panel/getMessageList".to_owned(),
+                Self::MessageRead { .. } => "panel/setMessageRead".to_owned(),
+                Self::PlantNoticeSet { .. } => "panel/getPlantNoticeSet".to_owned(),
+                Self::SetPlantNoticeSet { .. } => "panel/setPlantNoticeSet".to_owned(),
+                Self::MixSohHistory { .. } => "panel/mix/getMIXBatSohHistory".to_owned(),
+                Self::MixEnergyDayChart { .. } => "panel/mix/getMIXEnergyDayChart".to_owned(),
+                Self::MixSetParams { .. } => "panel/mix/getMIXSetParams".to_owned(),
+                Self::TcpSet { .. } => "tcpSet.do".to_owned(),
+                Self::MixHistory { .. } => "device/getMIXHistory".to_owned(),
+                Self::PlantCircuitLoad { .. } => "panel/getPlantCircuitLoad".to_owned(),
+                Self::DeviceInfo { .. } => "panel/getDeviceInfo".to_owned(),
+                Self::DeviceTypeList => "panel/getDeviceTypeList".to_owned(),
+                Self::PlantList => "index/getPlantListTitle".to_owned(),
+                Self::PlantFaultLog { .. } => "log/getNewPlantFaultLog".to_owned(),
+            }
+        }
+
+        /// Path in the OpenApi, `None` when it has no counterpart there
+        pub fn open_api_path(&self) -> Option<&'static str> {
+            match self {
+                Self::PlantList => Some("v1/plant/list"),
+                Self::PlantData { .. } => Some("v1/plant/details"),
+                Self::DevicesByPlant { .. } => Some("v1/device/list"),
+                Self::MixStatus { .. } => Some("v1/device/mix/mix_last_data"),
+                _ => None,
+            }
+        }
+
+        /// Query in the OpenApi, which takes every parameter in the query
+        pub fn open_api_query(&self) -> Vec<(&'static str, String)> {
+            match self {
+                Self::PlantData { plant_id } => vec![("plant_id", plant_id.to_string())],
+                Self::DevicesByPlant { plant_id, page } => vec![
+                    ("plant_id", plant_id.to_string()),
+                    ("page", page.to_string()),
+                ],
+                Self::MixStatus { mix_sn, .. } => vec![("mix_sn", mix_sn.to_string())],
+                _ => vec![],
+            }
+        }
+
+        pub fn query(&self) -> Vec<(&'static str, String)> {
+            match self {
+                Self::MixStatus { plant_id, .. }
+                | Self::MixTotal { plant_id, .. }
+                | Self::PlantData { plant_id }
+                | Self::PlantExpectEnergy { plant_id, .. }
+                | Self::DatalogInfo { plant_id, .. }
+                | Self::PlantNoticeSet { plant_id }
+                | Self::SetPlantNoticeSet { plant_id, .. }
+                | Self::MixSohHistory { plant_id, .. }
+                | Self::MixEnergyDayChart { plant_id, .. }
+                | Self::MixHistory { plant_id, .. }
+                | Self::PlantCircuitLoad { plant_id }
+                | Self::DeviceInfo { plant_id, .. }
+                | Self::PlantFaultLog { plant_id, .. } => vec![("plantId", plant_id.to_string())],
+                Self::DevicesByPlant { plant_id, page } => vec![
+                    ("plantId", plant_id.to_string()),
+                    ("currPage", page.to_string()),
+                ],
+                Self::MessageList { page } => vec![("currPage", page.to_string())],
+                Self::Login { .. }
+                | Self::PlantImage { .. }
+                | Self::MessageRead { .. }
+                | Self::MixSetParams { .. }
+                | Self::TcpSet { .. }
+                | Self::DeviceTypeList
+                | Self::PlantList => vec![],
+            }
+        }
+
+        pub fn form(&self) -> Vec<(String, String)> {
+            let flag = |enabled: bool| if enabled { "1" } else { "0" };
+            let pairs: Vec<(&str, String)> = match self {
+                Self::Login { account, password } => vec![
+                    ("account", account.to_string()),
+                    ("password", password.to_string()),
+                ],
+                Self::MixStatus { mix_sn, .. }
+                | Self::MixTotal { mix_sn, .. }
+                | Self::MixSetParams { mix_sn } => vec![("mixSn", mix_sn.to_string())],
+                Self::PlantExpectEnergy { date, .. } => vec![("date", format_date(*date))],
+                Self::DatalogInfo { datalog_sn, .. } => vec![("datalogSn", datalog_sn.to_string())],
+                Self::MessageRead { message_id } => vec![("id", message_id.to_string())],
+                Self::SetPlantNoticeSet { settings, .. } => vec![
+                    ("faultNotice", flag(settings.fault).to_owned()),
+                    ("warnNotice", flag(settings.warning).to_owned()),
+                    ("offlineNotice", flag(settings.offline).to_owned()),
+                    ("noticeEmail", settings.email.clone()),
+                ],
+                Self::MixSohHistory {
+                    mix_sn, start, end, ..
+                } => vec![
+                    ("mixSn", mix_sn.to_string()),
+                    ("startDate", format_date(*start)),
+                    ("endDate", format_date(*end)),
+                ],
+                Self::MixEnergyDayChart { mix_sn, date, .. } => {
+                    vec![("mixSn", mix_sn.to_string()), ("date", format_date(*date))]
+                }
+                Self::TcpSet {
+                    action,
+                    serial,
+                    setting_type,
+                    ..
+                } => vec![
+                    ("action", action.to_string()),
+                    ("serialNum", serial.to_string()),
+                    ("type", setting_type.to_string()),
+                ],
+                Self::MixHistory {
+                    mix_sn, date, page, ..
+                } => vec![
+                    ("mixSn", mix_sn.to_string()),
+                    ("startDate", format_date(*date)),
+                    ("endDate", format_date(*date)),
+                    ("start", page.to_string()),
+                ],
+                Self::DeviceInfo { device_sn, .. } => vec![("deviceSn", device_sn.to_string())],
+                Self::PlantFaultLog {
+                    start, end, page, ..
+                } => vec![
+                    ("startDate", format_date(*start)),
+                    ("endDate", format_date(*end)),
+                    ("toPageNum", page.to_string()),
+                ],
+                Self::DevicesByPlant { .. }
+                | Self::PlantData { .. }
+                | Self::PlantImage { .. }
+                | Self::MessageList { .. }
+                | Self::PlantNoticeSet { .. }
+                | Self::PlantCircuitLoad { .. }
+                | Self::DeviceTypeList
+                | Self::PlantList => vec![],
+            };
+
+            let mut form: Vec<(String, String)> = pairs
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect();
+            if let Self::TcpSet { params, .. } = self {
+                form.extend(
+                    params
+                        .iter()
+                        .enumerate()
+                        .map(|(i, param)| (format!("param{}", i + 1), param.to_string())),
+                );
+            }
+            form
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GrowattError {
+    Request(reqwest::Error),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+    Http {
+        status: u16,
+        body: String,
+    },
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// The portal is down for scheduled maintenance, `until` is the end time
+    /// stated on the maintenance page, if any
+    Maintenance {
+        until: Option<NaiveDateTime>,
+    },
+    InvalidArgument(String),
+    InvalidResponse(String),
+    /// A request ran past its timeout, or `poll_until` past its deadline
+    Timeout,
+}
+
+impl std::fmt::Display for GrowattError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "request failed: {}", e),
+            Self::Json(e) => write!(f, "unable to parse response: {}", e),
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Http { status, body } => write!(f, "http status {}: {}", status, body),
+            Self::RateLimited { retry_after } => {
+                write!(f, "rate limited, retry after {:?}", retry_after)
+            }
+            Self::Maintenance { until: Some(until) } => {
+                write!(f, "portal under maintenance until {}", until)
+            }
+            Self::Maintenance { until: None } => write!(f, "portal under maintenance"),
+            Self::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            Self::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            Self::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+impl std::error::Error for GrowattError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
+impl From<reqwest::Error> for GrowattError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Request(e)
+        }
+    }
+}
+
+impl From<std::io::Error> for GrowattError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for GrowattError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// How strictly responses are parsed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// A value that can not be parsed, or is out of range like a SOC above
+    /// 100, fails the whole call, except firmware dependent diagnostics
+    /// which are dropped with a warning. Fields the crate doesn't map are
+    /// ignored, the portal always sends plenty of them.
+    #[default]
+    Standard,
+    /// Like `Standard`, also failing on malformed diagnostics and on any
+    /// field the crate doesn't map. Meant to spot payload changes, not for
+    /// day to day use.
+    Strict,
+    /// Values that can not be parsed are replaced by a default (`0`, `None`,
+    /// `false`, a clamped value) and a warning is logged.
+    Lenient,
+}
+
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+const DEFAULT_ARCHIVE_PACING: Duration = Duration::from_millis(500);
+
+/// Transport used to reach Growatt, chosen when the client is built
+#[derive(Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Web portal with a cookie session, opened by `login`
+    WebPortal { server_url: String },
+    /// Official API authenticated with an account token. Only the methods
+    /// with a counterpart in that API are available (`plants`, `devices`,
+    /// `plant_info`, `mix_status` and the methods built on them), the others
+    /// return `GrowattError::InvalidArgument`.
+    OpenApi { token: String, base_url: String },
+}
+
+// Written by hand so that logging a backend doesn't leak the token
+impl Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WebPortal { server_url } => f
+                .debug_struct("WebPortal")
+                .field("server_url", server_url)
+                .finish(),
+            Self::OpenApi { base_url, .. } => f
+                .debug_struct("OpenApi")
+                .field("token", &"<redacted>")
+                .field("base_url", base_url)
+                .finish(),
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::WebPortal {
+            server_url: "https://server.growatt.com/".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct GrowattServer {
-    server_url: String,
+    backend: Backend,
     client: Client,
-    cookie: header::HeaderMap,
+    cookies: Vec<(String, String)>,
     referer: String,
+    language: Option<String>,
+    timeout: Option<Duration>,
+    max_retries: u32,
+    max_concurrency: usize,
+    archive_pacing: Duration,
+    parse_mode: ParseMode,
+    metadata: Arc<Mutex<HashMap<String, PlantMetadata>>>,
+    timezones: Arc<Mutex<HashMap<String, Option<FixedOffset>>>>,
 }
 
 impl Default for GrowattServer {
@@ -57,32 +835,48 @@ impl From<When> for i64 {
     }
 }
 
+impl<Tz: TimeZone> From<DateTime<Tz>> for When {
+    fn from(lhs: DateTime<Tz>) -> When {
+        Self(lhs.timestamp_nanos_opt().unwrap_or_default())
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
 pub struct MixStatus {
     #[serde(skip_deserializing)]
     pub when: When,
 
     #[serde(rename = "chargePower")]
+    #[serde(deserialize_with = "utils::from_str")]
     pub power_battery_charge: f32,
 
-    #[serde(deserialize_with = "utils::from_str")]
+    #[serde(deserialize_with = "utils::percent")]
     #[serde(rename = "SOC")]
     pub soc: u32,
 
     #[serde(rename = "pLocalLoad")]
+    #[serde(deserialize_with = "utils::from_str")]
     pub power_to_load: f32,
 
     #[serde(deserialize_with = "utils::from_str")]
     #[serde(rename = "pPv1")]
     pub power_from_photovoltaic_1: f32,
 
+    /// Second PV string, only reported by dual string models
+    #[serde(rename = "pPv2")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub power_from_photovoltaic_2: Option<f32>,
+
     #[serde(rename = "pactogrid")]
+    #[serde(deserialize_with = "utils::from_str")]
     pub power_to_grid: f32,
 
     #[serde(rename = "pactouser")]
+    #[serde(deserialize_with = "utils::from_str")]
     pub power_to_user: f32,
 
     #[serde(rename = "pdisCharge1")]
+    #[serde(deserialize_with = "utils::from_str")]
     pub power_battery_discharge: f32,
 
     #[serde(rename = "vAc1")]
@@ -96,161 +890,2419 @@ pub struct MixStatus {
     #[serde(rename = "vPv1")]
     #[serde(deserialize_with = "utils::from_str")]
     pub voltage_photovoltaic_1: f32,
-}
-
-impl GrowattServer {
-    pub fn new() -> Self {
-        Self {
-            server_url: "https://server.growatt.com/".to_owned(),
-            referer: "".to_owned(),
-            client: Client::builder().build().unwrap(),
-            cookie: header::HeaderMap::new(),
-        }
-    }
 
-    fn check_res(body: String) -> bool {
-        let parse_check = serde_json::from_str::<Value>(&body)
-            .ok()
-            .and_then(|v| v.get("result").and_then(|value| value.as_i64()))
-            .map(|num| if num == 0 { false } else { true })
-            .unwrap_or(false);
+    #[serde(rename = "antiBackflowStatus")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub anti_backflow_status: Option<u8>,
 
-        parse_check
-    }
+    #[serde(rename = "bmsProtectStatus")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub battery_protection_status: Option<u8>,
 
-    fn get_url(&self, page: &str) -> String {
-        let mut ret = self.server_url.clone();
-        ret.push_str(page);
-        ret
-    }
+    #[serde(rename = "iso")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub insulation_resistance: Option<f32>,
 
-    pub async fn login(
-        &mut self,
-        username: &str,
-        password: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let url = self.get_url("login");
+    #[serde(rename = "gfci")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub leakage_current: Option<f32>,
 
-        let mut headers = header::HeaderMap::new();
-        headers.insert("User-Agent", header::HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/104.0.0.0 Safari/537.36-11"));
-        headers.insert("Connection", header::HeaderValue::from_static("keep-alive"));
+    #[serde(rename = "deratingMode")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub derating_mode: Option<u8>,
 
-        let payload: HashMap<&str, &str> =
-            HashMap::from([("account", username), ("password", password)]);
+    /// Warning bitmask, decoded by `warnings`
+    #[serde(rename = "warnCode")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub warn_code: Option<u32>,
 
-        let res = self
-            .client
-            .post(url)
-            .headers(headers)
-            .form(&payload)
-            .send()
-            .await?;
+    /// Conversion efficiency in percent, when the firmware reports it
+    #[serde(rename = "efficiency")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub efficiency: Option<f32>,
 
-        log::trace!("login request with status {}", res.status().as_str());
+    /// Charge current the BMS currently allows, it drops with temperature
+    /// and SOC
+    #[serde(rename = "bmsMaxChargeCurr")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub allowed_charge_current: Option<f32>,
 
-        let re_session = Regex::new(r"JSESSIONID=([^;]+)").unwrap();
-        let se_session = Regex::new(r"SERVERID=").unwrap();
+    #[serde(rename = "bmsMaxDischargeCurr")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub allowed_discharge_current: Option<f32>,
 
-        self.cookie.clear();
-        for el in res.headers().get_all("set-cookie") {
-            let current_cookie = el.to_str()?;
-            log::trace!("using cookie {}", current_cookie);
+    /// Full charge cycles counted by the BMS, compare it with the warranted
+    /// cycle life
+    #[serde(rename = "bmsCycleCount", alias = "cycleCount")]
+    #[serde(default, deserialize_with = "utils::option_diagnostic")]
+    pub cycle_count: Option<u32>,
 
-            if let Some(caps) = re_session.captures(current_cookie) {
-                self.referer = format!(
-                    "https://server.growatt.com/index;jsessionid={}",
-                    caps[1].to_owned()
-                );
-                self.cookie.append("cookie", el.clone());
-            }
+    /// When the datalogger recorded the sample, in the plant timezone
+    #[serde(rename = "lastUpdateTime")]
+    #[serde(default, deserialize_with = "utils::option_datetime")]
+    pub sample_time: Option<NaiveDateTime>,
+}
 
-            if let Some(_) = se_session.captures(current_cookie) {
-                self.cookie.append("cookie", el.clone());
-            }
-        }
+/// Names the 5 minutes history uses for the `MixStatus` fields. They can't be
+/// serde aliases, the live status carries some of them next to the usual
+/// spelling (e.g. both `vAc1` and `vac1`).
+const HISTORY_KEYS: &[(&str, &str)] = &[
+    ("pcharge1", "chargePower"),
+    ("soc", "SOC"),
+    ("pLocalLoad1", "pLocalLoad"),
+    ("ppv1", "pPv1"),
+    ("ppv2", "pPv2"),
+    ("pacToGridTotal", "pactogrid"),
+    ("pacToUserTotal", "pactouser"),
+    ("pdischarge1", "pdisCharge1"),
+    ("vac1", "vAc1"),
+    ("vbat", "vBat"),
+    ("vpv1", "vPv1"),
+    ("time", "lastUpdateTime"),
+];
 
-        let body = res.text().await?;
+/// Live status paired with the rated power of its device, for "percent of
+/// rated" gauges. `rated_power` is in kW, the unit of the status powers.
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct ScaledStatus {
+    pub status: MixStatus,
+    pub rated_power: f32,
+}
 
-        if Self::check_res(body.clone()) == false {
-            Err(
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "Missing success field")
-                    .into(),
-            )
-        } else {
-            Ok(body)
+impl ScaledStatus {
+    pub fn new(status: MixStatus, rated_power: f32) -> Self {
+        Self {
+            status,
+            rated_power,
         }
     }
 
-    pub async fn mix_system_status(
-        &self,
-        mix_id: &str,
-        plant_id: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let url = format!("panel/mix/getMIXStatusData?plantId={}", plant_id);
-        let url = self.get_url(&url);
+    fn percent(&self, power: f32) -> Option<f32> {
+        (self.rated_power > 0.0).then(|| power / self.rated_power * 100.0)
+    }
 
-        let mut payload = HashMap::new();
-        payload.insert("mixSn", mix_id);
+    pub fn pv_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_from_photovoltaic())
+    }
 
-        let mut hm = header::HeaderMap::new();
-        hm.insert("Referer", self.referer.parse().unwrap());
+    pub fn load_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_to_load)
+    }
 
-        let res = self
-            .client
-            .post(url)
-            .headers(self.cookie.clone())
-            .headers(hm)
-            .form(&payload)
-            .send()
-            .await?;
+    pub fn export_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_to_grid)
+    }
 
-        log::trace!(
-            "mix_system_status request with status {}",
-            res.status().as_str()
-        );
+    pub fn import_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_to_user)
+    }
 
-        let content = res.text().await?;
+    pub fn charge_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_battery_charge)
+    }
 
-        //Strip off unusefull part
-        let v =
-            serde_json::from_str(&content).and_then(|v: Value| serde_json::to_string(&v["obj"]))?;
-        Ok(v)
+    pub fn discharge_percent(&self) -> Option<f32> {
+        self.percent(self.status.power_battery_discharge)
     }
+}
 
-    pub async fn device_list_by_plant(
-        &self,
-        plant_id: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let url = format!(
-            "panel/getDevicesByPlantList?plantId={}&currPage=1",
-            plant_id
+/// Plant level view summed over the devices of a plant
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlantAggregate {
+    pub photovoltaic: f32,
+    pub load: f32,
+    pub grid_import: f32,
+    pub grid_export: f32,
+    pub battery_charge: f32,
+    pub battery_discharge: f32,
+    /// Weighted average SOC, `None` when there are no samples
+    pub soc: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeratingReason {
+    None,
+    OverTemperature,
+    OverFrequency,
+    OverVoltage,
+    PowerLimit,
+    PhotovoltaicInput,
+    Other(u8),
+}
+
+impl From<u8> for DeratingReason {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::None,
+            1 => Self::OverTemperature,
+            2 => Self::OverFrequency,
+            3 => Self::OverVoltage,
+            4 => Self::PowerLimit,
+            5 => Self::PhotovoltaicInput,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Warning set in `MixStatus::warn_code`, `Other` holds the bit number of
+/// the warnings not known yet
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    Fan,
+    Communication,
+    MeterCommunication,
+    PhotovoltaicString,
+    OverTemperature,
+    BatteryCommunication,
+    Eeprom,
+    Other(u8),
+}
+
+impl Warning {
+    /// Every warning set in `mask`, lowest bit first
+    pub fn from_mask(mask: u32) -> Vec<Warning> {
+        (0..32)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| match bit {
+                0 => Self::Fan,
+                1 => Self::Communication,
+                2 => Self::MeterCommunication,
+                3 => Self::PhotovoltaicString,
+                4 => Self::OverTemperature,
+                5 => Self::BatteryCommunication,
+                6 => Self::Eeprom,
+                other => Self::Other(other),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryProtection {
+    None,
+    OverTemperature,
+    LowTemperatureChargeInhibit,
+    OverVoltage,
+    UnderVoltage,
+    OverCurrent,
+    Other(u8),
+}
+
+impl From<u8> for BatteryProtection {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::None,
+            1 => Self::OverTemperature,
+            2 => Self::LowTemperatureChargeInhibit,
+            3 => Self::OverVoltage,
+            4 => Self::UnderVoltage,
+            5 => Self::OverCurrent,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl MixStatus {
+    /// Whether both statuses come from the same datalogger upload
+    pub fn is_same_sample(&self, other: &MixStatus) -> bool {
+        self.sample_time.is_some() && self.sample_time == other.sample_time
+    }
+
+    /// PV power summed over the strings
+    pub fn power_from_photovoltaic(&self) -> f32 {
+        self.power_from_photovoltaic_1 + self.power_from_photovoltaic_2.unwrap_or(0.0)
+    }
+
+    /// Combine the status of several devices, giving every battery the same
+    /// weight in the SOC. Use `aggregate_weighted` when capacities differ.
+    pub fn aggregate(samples: &[MixStatus]) -> PlantAggregate {
+        let weighted: Vec<(MixStatus, f32)> = samples.iter().map(|s| (*s, 1.0)).collect();
+        Self::aggregate_weighted(&weighted)
+    }
+
+    /// Combine the status of several devices, each paired with its battery
+    /// capacity so that the SOC is the share of the total energy stored.
+    pub fn aggregate_weighted(samples: &[(MixStatus, f32)]) -> PlantAggregate {
+        let mut aggregate = PlantAggregate::default();
+        let mut stored = 0.0;
+        let mut capacity = 0.0;
+
+        for (sample, battery_capacity) in samples {
+            aggregate.photovoltaic += sample.power_from_photovoltaic();
+            aggregate.load += sample.power_to_load;
+            aggregate.grid_import += sample.power_to_user;
+            aggregate.grid_export += sample.power_to_grid;
+            aggregate.battery_charge += sample.power_battery_charge;
+            aggregate.battery_discharge += sample.power_battery_discharge;
+
+            stored += sample.soc as f32 * battery_capacity;
+            capacity += battery_capacity;
+        }
+
+        if capacity > 0.0 {
+            aggregate.soc = Some(stored / capacity);
+        }
+
+        aggregate
+    }
+
+    /// Whether anti-backflow (zero export) is currently clamping the output.
+    /// Firmwares that don't report it are considered not limited.
+    pub fn is_export_limited(&self) -> bool {
+        matches!(self.anti_backflow_status, Some(status) if status != 0)
+    }
+
+    /// Protection state reported by the BMS, `None` when the firmware
+    /// doesn't report it.
+    pub fn battery_protection(&self) -> Option<BatteryProtection> {
+        self.battery_protection_status.map(BatteryProtection::from)
+    }
+
+    /// Why the inverter is derating, `None` when the firmware doesn't report
+    /// it.
+    pub fn derating_reason(&self) -> Option<DeratingReason> {
+        self.derating_mode.map(DeratingReason::from)
+    }
+
+    /// Active warnings, empty when there are none or the firmware doesn't
+    /// report them.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warn_code.map(Warning::from_mask).unwrap_or_default()
+    }
+}
+
+/// Spots samples the portal returns again while the datalogger has not
+/// uploaded anything new, by comparing their `sample_time`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StaleSampleFilter {
+    last: Option<NaiveDateTime>,
+}
+
+impl StaleSampleFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `true` when `sample` was recorded at the same time as, or before, the
+    /// previous fresh one. Samples without a sample time are never stale.
+    pub fn is_stale(&mut self, sample: &MixStatus) -> bool {
+        let Some(time) = sample.sample_time else {
+            return false;
+        };
+
+        if matches!(self.last, Some(last) if time <= last) {
+            return true;
+        }
+
+        self.last = Some(time);
+        false
+    }
+}
+
+/// Running energy tally built by integrating successive `MixStatus` samples
+/// with the trapezoidal rule over their `when` deltas.
+///
+/// Energies are power × hours, so kWh as the portal reports MIX powers in
+/// kW.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EnergyAccumulator {
+    last: Option<MixStatus>,
+    pub photovoltaic: f64,
+    pub load: f64,
+    pub battery_charge: f64,
+    pub battery_discharge: f64,
+    pub grid_import: f64,
+    pub grid_export: f64,
+}
+
+impl EnergyAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sample: &MixStatus) {
+        if let Some(last) = self.last {
+            let dt_ns = i64::from(sample.when) - i64::from(last.when);
+            if dt_ns <= 0 {
+                // Out of order or duplicated sample, nothing to integrate
+                return;
+            }
+
+            let hours = dt_ns as f64 / 3_600_000_000_000.0;
+            let area = |a: f32, b: f32| (a as f64 + b as f64) / 2.0 * hours;
+
+            self.photovoltaic += area(
+                last.power_from_photovoltaic(),
+                sample.power_from_photovoltaic(),
+            );
+            self.load += area(last.power_to_load, sample.power_to_load);
+            self.battery_charge += area(last.power_battery_charge, sample.power_battery_charge);
+            self.battery_discharge +=
+                area(last.power_battery_discharge, sample.power_battery_discharge);
+            self.grid_import += area(last.power_to_user, sample.power_to_user);
+            self.grid_export += area(last.power_to_grid, sample.power_to_grid);
+        }
+
+        self.last = Some(*sample);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Energy counters of a MIX, in kWh
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct MixTotal {
+    #[serde(rename = "epvToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub photovoltaic_today: Option<f32>,
+
+    #[serde(rename = "epvTotal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub photovoltaic_total: Option<f32>,
+
+    #[serde(rename = "epv1Today")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub photovoltaic_1_today: Option<f32>,
+
+    #[serde(rename = "epv2Today")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub photovoltaic_2_today: Option<f32>,
+
+    #[serde(rename = "eToUserToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub import_today: Option<f32>,
+
+    #[serde(rename = "eToUserTotal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub import_total: Option<f32>,
+
+    #[serde(rename = "eToGridToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub export_today: Option<f32>,
+
+    #[serde(rename = "eToGridTotal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub export_total: Option<f32>,
+
+    #[serde(rename = "elocalLoadToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub load_energy_today: Option<f32>,
+
+    #[serde(rename = "elocalLoadTotal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub load_energy_total: Option<f32>,
+}
+
+/// Power curves of a MIX for a single day, one sample every
+/// `MixDayChart::INTERVAL` starting at midnight.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct MixDayChart {
+    #[serde(rename = "ppv")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub photovoltaic: Vec<Option<f32>>,
+
+    #[serde(rename = "userLoad")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub load: Vec<Option<f32>>,
+
+    #[serde(rename = "pacToUser")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub grid_import: Vec<Option<f32>>,
+
+    #[serde(rename = "pacToGrid")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub grid_export: Vec<Option<f32>>,
+
+    #[serde(rename = "pcharge")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub battery_charge: Vec<Option<f32>>,
+
+    #[serde(rename = "pdisCharge")]
+    #[serde(default, deserialize_with = "utils::series")]
+    pub battery_discharge: Vec<Option<f32>>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct DaySummary {
+    pub peak_photovoltaic: f32,
+    pub peak_time: Option<NaiveTime>,
+    /// Power × hours of the photovoltaic curve
+    pub photovoltaic_energy: f32,
+}
+
+impl MixDayChart {
+    pub const INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+    pub fn time_of(index: usize) -> Option<NaiveTime> {
+        let secs = index as u64 * Self::INTERVAL.as_secs();
+        NaiveTime::from_num_seconds_from_midnight_opt(u32::try_from(secs).ok()?, 0)
+    }
+
+    pub fn summary(&self) -> DaySummary {
+        let hours = Self::INTERVAL.as_secs_f32() / 3600.0;
+        let mut summary = DaySummary::default();
+
+        for (idx, power) in self.photovoltaic.iter().enumerate() {
+            let Some(power) = *power else { continue };
+
+            summary.photovoltaic_energy += power * hours;
+            if summary.peak_time.is_none() || power > summary.peak_photovoltaic {
+                summary.peak_photovoltaic = power;
+                summary.peak_time = Self::time_of(idx);
+            }
+        }
+
+        summary
+    }
+
+    /// Power × hours under a series of the chart
+    pub fn energy(series: &[Option<f32>]) -> f32 {
+        let hours = Self::INTERVAL.as_secs_f32() / 3600.0;
+        series.iter().flatten().sum::<f32>() * hours
+    }
+}
+
+/// Share of the load covered on site (self-sufficiency) and share of the
+/// production used on site (self-consumption), between 0 and 1.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SelfRates {
+    pub photovoltaic_energy: f32,
+    pub load_energy: f32,
+    pub grid_import_energy: f32,
+    pub grid_export_energy: f32,
+    pub self_sufficiency: Option<f32>,
+    pub self_consumption: Option<f32>,
+}
+
+impl SelfRates {
+    pub fn from_energies(photovoltaic: f32, load: f32, grid_import: f32, grid_export: f32) -> Self {
+        let rate =
+            |part: f32, total: f32| (total > 0.0).then(|| ((total - part) / total).clamp(0.0, 1.0));
+
+        Self {
+            photovoltaic_energy: photovoltaic,
+            load_energy: load,
+            grid_import_energy: grid_import,
+            grid_export_energy: grid_export,
+            self_sufficiency: rate(grid_import, load),
+            self_consumption: rate(grid_export, photovoltaic),
+        }
+    }
+}
+
+/// Grid voltage and frequency over a day, the three series are aligned
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct GridQualitySeries {
+    pub times: Vec<NaiveDateTime>,
+    pub voltage: Vec<Option<f32>>,
+    pub frequency: Vec<Option<f32>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct DataloggerDetail {
+    #[serde(rename = "datalogSn")]
+    pub serial: String,
+
+    #[serde(rename = "lastUpdateTime")]
+    #[serde(default)]
+    pub last_update: String,
+
+    #[serde(rename = "ipAndPort")]
+    #[serde(default)]
+    pub ip: Option<String>,
+
+    #[serde(rename = "wifiSsid")]
+    #[serde(default)]
+    pub ssid: Option<String>,
+
+    #[serde(rename = "signal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub rssi: Option<i32>,
+
+    /// Upload interval in minutes
+    #[serde(rename = "interval")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub interval: Option<u32>,
+}
+
+impl DataloggerDetail {
+    pub fn upload_interval(&self) -> Option<Duration> {
+        self.interval
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes as u64 * 60))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct DataloggerFirmware {
+    #[serde(rename = "datalogSn")]
+    pub serial: String,
+
+    #[serde(rename = "firmwareVersion")]
+    #[serde(default)]
+    pub version: String,
+
+    /// Latest version known to the portal, set once an update check ran
+    #[serde(rename = "newFirmwareVersion")]
+    #[serde(default)]
+    pub latest_version: Option<String>,
+}
+
+impl DataloggerFirmware {
+    pub fn update_available(&self) -> bool {
+        matches!(&self.latest_version, Some(latest) if !latest.is_empty() && *latest != self.version)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct NotificationSettings {
+    #[serde(rename = "faultNotice")]
+    #[serde(deserialize_with = "utils::flag")]
+    pub fault: bool,
+
+    #[serde(rename = "warnNotice")]
+    #[serde(deserialize_with = "utils::flag")]
+    pub warning: bool,
+
+    #[serde(rename = "offlineNotice")]
+    #[serde(deserialize_with = "utils::flag")]
+    pub offline: bool,
+
+    #[serde(rename = "noticeEmail")]
+    #[serde(default)]
+    pub email: String,
+}
+
+/// Stable hash of the set of keys present in a response, values are ignored.
+///
+/// Nested keys are taken into account by their full path and array items are
+/// merged together, so the fingerprint only moves when the portal adds,
+/// removes or renames a field.
+pub fn response_fingerprint(value: &Value) -> u64 {
+    fn collect(value: &Value, prefix: &str, keys: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                for (k, v) in map {
+                    let path = format!("{}.{}", prefix, k);
+                    collect(v, &path, keys);
+                    keys.insert(path);
+                }
+            }
+            Value::Array(items) => {
+                let path = format!("{}[]", prefix);
+                for item in items {
+                    collect(item, &path, keys);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut keys = BTreeSet::new();
+    collect(value, "", &mut keys);
+
+    // FNV-1a, std hashers are not guaranteed to be stable across releases
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for key in keys {
+        for byte in key.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    #[serde(rename = "inv")]
+    Inverter,
+    #[serde(rename = "mix")]
+    Mix,
+    #[serde(rename = "storage")]
+    Storage,
+    #[serde(rename = "tlx")]
+    Tlx,
+    #[serde(rename = "max")]
+    Max,
+    #[serde(rename = "spa")]
+    Spa,
+    #[serde(rename = "sph")]
+    Sph,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+const SERIAL_PREFIXES: &[(&str, DeviceType)] = &[
+    ("MIX", DeviceType::Mix),
+    ("SPH", DeviceType::Sph),
+    ("SPA", DeviceType::Spa),
+    ("MIN", DeviceType::Tlx),
+    ("MOD", DeviceType::Tlx),
+    ("MID", DeviceType::Tlx),
+    ("TLX", DeviceType::Tlx),
+    ("MAX", DeviceType::Max),
+    ("SPF", DeviceType::Storage),
+    ("MIC", DeviceType::Inverter),
+];
+
+impl DeviceType {
+    pub fn has_storage(&self) -> bool {
+        matches!(self, Self::Mix | Self::Storage | Self::Spa | Self::Sph)
+    }
+
+    /// Best effort guess of the device family from its serial prefix, with
+    /// no network call. `Unknown` when the prefix is not recognized.
+    pub fn from_serial(sn: &str) -> DeviceType {
+        let sn = sn.trim().to_ascii_uppercase();
+        SERIAL_PREFIXES
+            .iter()
+            .find(|(prefix, _)| sn.starts_with(prefix))
+            .map(|(_, device_type)| *device_type)
+            .unwrap_or(DeviceType::Unknown)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Device {
+    #[serde(rename = "sn", alias = "device_sn")]
+    pub serial: String,
+
+    #[serde(rename = "deviceType")]
+    #[serde(default)]
+    pub device_type: DeviceType,
+
+    #[serde(default)]
+    pub alias: String,
+
+    #[serde(rename = "datalogSn", alias = "datalogger_sn")]
+    #[serde(default)]
+    pub datalog_sn: String,
+
+    #[serde(default, deserialize_with = "utils::from_str")]
+    pub status: i32,
+
+    #[serde(rename = "pac")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub power: Option<f32>,
+
+    #[serde(rename = "eToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub energy_today: Option<f32>,
+
+    #[serde(rename = "nominalPower")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub nominal_power: Option<f32>,
+
+    #[serde(rename = "lastUpdateTime", alias = "last_update_time")]
+    #[serde(default)]
+    pub last_update: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct DeviceDetail {
+    #[serde(rename = "sn")]
+    pub serial: String,
+
+    #[serde(rename = "deviceModel")]
+    #[serde(default)]
+    pub model: String,
+
+    #[serde(rename = "fwVersion")]
+    #[serde(default)]
+    pub firmware_version: String,
+
+    #[serde(rename = "nominalPower")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub nominal_power: Option<f32>,
+
+    /// Cumulative operating hours
+    #[serde(rename = "timeTotal")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub operating_hours: Option<f32>,
+}
+
+/// Entry of the portal model catalog, `code` is what `DeviceDetail::model`
+/// holds
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct DeviceModel {
+    #[serde(rename = "modelCode", alias = "model")]
+    pub code: String,
+
+    #[serde(rename = "modelName", alias = "name")]
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(rename = "deviceType", alias = "type")]
+    #[serde(default)]
+    pub device_type: DeviceType,
+
+    #[serde(rename = "nominalPower")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub nominal_power: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceStatus {
+    Offline,
+    Waiting,
+    Normal,
+    Fault,
+    Other(i32),
+}
+
+impl From<i32> for DeviceStatus {
+    fn from(code: i32) -> Self {
+        match code {
+            -1 => Self::Offline,
+            0 => Self::Waiting,
+            1 => Self::Normal,
+            3 => Self::Fault,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl Device {
+    pub fn is_online(&self) -> bool {
+        self.status != -1
+    }
+
+    pub fn device_status(&self) -> DeviceStatus {
+        DeviceStatus::from(self.status)
+    }
+}
+
+/// Devices of a plant counted by status
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceHealthCounts {
+    pub online: usize,
+    pub offline: usize,
+    pub fault: usize,
+}
+
+impl DeviceHealthCounts {
+    pub fn from_devices(devices: &[Device]) -> Self {
+        let mut counts = Self::default();
+        for device in devices {
+            match device.device_status() {
+                DeviceStatus::Offline => counts.offline += 1,
+                DeviceStatus::Fault => counts.fault += 1,
+                _ => counts.online += 1,
+            }
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.online + self.offline + self.fault
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct PlantInfo {
+    #[serde(rename = "plantName", alias = "name")]
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub city: String,
+
+    #[serde(default)]
+    pub country: String,
+
+    #[serde(default)]
+    pub timezone: String,
+
+    #[serde(rename = "nominalPower", alias = "peak_power")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub nominal_power: Option<f32>,
+
+    #[serde(rename = "plantImgName")]
+    #[serde(default)]
+    pub image_name: Option<String>,
+
+    /// Price per kWh the portal applies to the whole production for its
+    /// "money saved" figure, there is no separate feed-in tariff.
+    #[serde(rename = "formulaMoney")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub tariff: Option<f32>,
+
+    #[serde(rename = "moneyUnit")]
+    #[serde(default)]
+    pub currency: String,
+
+    /// kg of CO2 saved per kWh
+    #[serde(rename = "formulaCo2")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub co2_factor: Option<f32>,
+}
+
+impl PlantInfo {
+    /// Offset of the plant timezone, which the portal reports as hours from
+    /// UTC (e.g. `"1"`, `"-5"`, `"+5.5"`)
+    pub fn utc_offset(&self) -> Option<FixedOffset> {
+        let tz = self
+            .timezone
+            .trim()
+            .trim_start_matches("GMT")
+            .trim_start_matches("UTC");
+        let hours: f32 = tz.parse().ok()?;
+        FixedOffset::east_opt((hours * 3600.0).round() as i32)
+    }
+
+    /// Money saved for the given production, computed as the portal does
+    pub fn savings(&self, energy_kwh: f32) -> Option<f32> {
+        self.tariff.map(|tariff| tariff * energy_kwh)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct GenerationComparison {
+    #[serde(rename = "actualEnergy")]
+    #[serde(deserialize_with = "utils::from_str")]
+    pub actual: f32,
+
+    #[serde(rename = "expectEnergy")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub expected: Option<f32>,
+}
+
+impl GenerationComparison {
+    /// Actual over expected generation, `None` when the portal has no
+    /// usable expectation for the day.
+    pub fn performance_ratio(&self) -> Option<f32> {
+        self.expected
+            .filter(|expected| *expected > 0.0)
+            .map(|expected| self.actual / expected)
+    }
+}
+
+/// Plant details that rarely change, cached by `plant_metadata`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PlantMetadata {
+    pub info: PlantInfo,
+    pub devices: Vec<Device>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlantLive {
+    pub power: f32,
+    /// Mean SOC across the online MIX devices, `None` without any
+    pub soc: Option<f32>,
+    pub devices_online: usize,
+    pub devices_total: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct CircuitLoad {
+    #[serde(rename = "circuitName")]
+    pub name: String,
+
+    #[serde(rename = "pac")]
+    #[serde(deserialize_with = "utils::from_str")]
+    pub power: f32,
+
+    #[serde(rename = "eToday")]
+    #[serde(default, deserialize_with = "utils::option_from_str")]
+    pub energy_today: Option<f32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct InboxMessage {
+    #[serde(deserialize_with = "utils::from_str")]
+    pub id: u64,
+
+    #[serde(default)]
+    pub title: String,
+
+    #[serde(default)]
+    pub content: String,
+
+    #[serde(rename = "createTime")]
+    #[serde(default)]
+    pub time: String,
+
+    #[serde(rename = "isRead")]
+    #[serde(default, deserialize_with = "utils::flag")]
+    pub read: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Plant {
+    #[serde(alias = "plant_id", deserialize_with = "utils::from_str")]
+    pub id: String,
+
+    #[serde(rename = "plantName", alias = "name")]
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Entry of a plant fault log, `plant_id` tells which plant it comes from
+/// when logs of several plants are merged
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct FaultEvent {
+    #[serde(default)]
+    pub plant_id: String,
+
+    #[serde(rename = "deviceSn")]
+    #[serde(default)]
+    pub device_sn: String,
+
+    #[serde(rename = "eventId")]
+    #[serde(default, deserialize_with = "utils::from_str")]
+    pub code: String,
+
+    #[serde(rename = "eventName")]
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(deserialize_with = "utils::datetime")]
+    pub time: NaiveDateTime,
+}
+
+/// Fault feed of a whole account, see `GrowattServer::account_faults`
+#[derive(Debug, Default)]
+pub struct AccountFaults {
+    pub events: Vec<FaultEvent>,
+    /// Plants whose fault log could not be read, with the reason
+    pub failed_plants: Vec<(String, GrowattError)>,
+}
+
+impl GrowattServer {
+    pub fn new() -> Self {
+        Self::with_backend(Backend::default())
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            referer: "".to_owned(),
+            client: Client::builder().build().unwrap(),
+            cookies: Vec::new(),
+            language: None,
+            timeout: None,
+            max_retries: 0,
+            max_concurrency: 4,
+            archive_pacing: DEFAULT_ARCHIVE_PACING,
+            parse_mode: ParseMode::Standard,
+            metadata: Arc::new(Mutex::new(HashMap::new())),
+            timezones: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// How values that can not be parsed are handled, see `ParseMode`
+    pub fn set_parse_mode(&mut self, parse_mode: ParseMode) {
+        self.parse_mode = parse_mode;
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(&self, value: Value) -> Result<T, GrowattError> {
+        utils::with_parse_mode(self.parse_mode, || match self.parse_mode {
+            ParseMode::Strict => Ok(T::deserialize(utils::Strict::new(value))?),
+            ParseMode::Standard | ParseMode::Lenient => Ok(serde_json::from_value(value)?),
+        })
+    }
+
+    /// Maximum number of requests in flight for the calls fanning out over
+    /// several plants, 4 by default
+    pub fn set_max_concurrency(&mut self, max_concurrency: usize) {
+        self.max_concurrency = max_concurrency.max(1);
+    }
+
+    /// Pause before each request of `export_plant_archive`, 500 ms by default
+    pub fn set_archive_pacing(&mut self, pacing: Duration) {
+        self.archive_pacing = pacing;
+    }
+
+    /// How many times a request rejected with HTTP 429 is retried, waiting
+    /// for the `Retry-After` delay asked by the server. With no retries left
+    /// `GrowattError::RateLimited` is returned.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Timeout applied to every request, no timeout by default. Expired
+    /// requests fail with `GrowattError::Timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Copy of the client sharing the same session, with a different timeout.
+    /// Handy to give a single slow call more time, e.g.
+    /// `client.with_timeout(Duration::from_secs(60)).plant_info(id)`.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.timeout = Some(timeout);
+        client
+    }
+
+    fn with_request_timeout(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        }
+    }
+
+    fn post(&self, endpoint: &Endpoint) -> Result<reqwest::RequestBuilder, GrowattError> {
+        let req = self
+            .client
+            .post(self.get_url(endpoint)?)
+            .query(&endpoint.query())
+            .form(&endpoint.form())
+            .headers(self.session_headers());
+        Ok(self.with_request_timeout(req))
+    }
+
+    fn get(&self, endpoint: &Endpoint) -> Result<reqwest::RequestBuilder, GrowattError> {
+        let query = match self.backend {
+            Backend::WebPortal { .. } => endpoint.query(),
+            Backend::OpenApi { .. } => endpoint.open_api_query(),
+        };
+        let req = self
+            .client
+            .get(self.get_url(endpoint)?)
+            .query(&query)
+            .headers(self.session_headers());
+        Ok(self.with_request_timeout(req))
+    }
+
+    /// Send `endpoint`, posted to the web portal and fetched with a GET from
+    /// the OpenApi, and read the response body
+    async fn request(&self, endpoint: Endpoint<'_>) -> Result<String, GrowattError> {
+        let req = match self.backend {
+            Backend::WebPortal { .. } => self.post(&endpoint)?,
+            Backend::OpenApi { .. } => self.get(&endpoint)?,
+        };
+        let res = self.send(req).await?;
+
+        log::trace!(
+            "{} request with status {}",
+            endpoint.path(),
+            res.status().as_str()
         );
-        let url = self.get_url(&url);
 
-        let mut hm = header::HeaderMap::new();
-        hm.insert("Referer", self.referer.parse().unwrap());
+        Self::read_body(res).await
+    }
+
+    /// Payload of a successful response to `endpoint`
+    async fn fetch_obj(&self, endpoint: Endpoint<'_>) -> Result<Value, GrowattError> {
+        let content = self.request(endpoint).await?;
+        if let Backend::OpenApi { .. } = self.backend {
+            return Self::open_api_data(&content);
+        }
+        if !Self::check_res(content.clone()) {
+            return Err(GrowattError::InvalidResponse("Succeed false".to_owned()));
+        }
+
+        Self::extract_obj(&content)
+    }
+
+    /// Ask the portal to localize descriptive strings (fault descriptions,
+    /// statuses, ...) in the given language, e.g. `"en"`, regardless of the
+    /// locale configured on the account.
+    pub fn set_language(&mut self, language: &str) {
+        self.language = Some(language.to_owned());
+    }
+
+    /// Single `Cookie` header with the session cookies and the language
+    fn cookie_header(&self) -> Option<header::HeaderValue> {
+        let mut pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if let Some(lang) = &self.language {
+            pairs.push(format!("lang={}", lang));
+        }
+
+        if pairs.is_empty() {
+            return None;
+        }
+        pairs.join("; ").parse().ok()
+    }
+
+    fn session_headers(&self) -> header::HeaderMap {
+        let mut hm = header::HeaderMap::new();
+        match &self.backend {
+            Backend::WebPortal { .. } => {
+                hm.insert("Referer", self.referer.parse().unwrap());
+                if let Some(cookie) = self.cookie_header() {
+                    hm.insert(header::COOKIE, cookie);
+                }
+            }
+            Backend::OpenApi { token, .. } => {
+                if let Ok(token) = token.parse() {
+                    hm.insert("token", token);
+                }
+            }
+        }
+        hm
+    }
+
+    fn check_res(body: String) -> bool {
+        let parse_check = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|v| v.get("result").and_then(|value| value.as_i64()))
+            .map(|num| num != 0)
+            .unwrap_or(false);
+
+        parse_check
+    }
+
+    /// Payload of a response, some devices double encode it as a JSON
+    /// string so one level of encoding is transparently removed.
+    fn extract_obj(content: &str) -> Result<Value, GrowattError> {
+        let mut v: Value = serde_json::from_str(content)?;
+        let obj = v["obj"].take();
+
+        if let Value::String(inner) = &obj {
+            if let Ok(decoded @ (Value::Object(_) | Value::Array(_))) =
+                serde_json::from_str::<Value>(inner)
+            {
+                return Ok(decoded);
+            }
+        }
+
+        Ok(obj)
+    }
+
+    fn check_success(body: &str) -> bool {
+        serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|v| v.get("success").and_then(|value| value.as_bool()))
+            .unwrap_or(false)
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, GrowattError> {
+        let mut retries = self.max_retries;
+
+        loop {
+            let attempt = req.try_clone().ok_or_else(|| {
+                GrowattError::InvalidResponse("Request can not be retried".to_owned())
+            })?;
+            let res = attempt.send().await?;
+
+            if res.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(res);
+            }
+
+            let retry_after = res
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(utils::parse_retry_after)
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+
+            if retries == 0 {
+                return Err(GrowattError::RateLimited { retry_after });
+            }
+            retries -= 1;
+
+            log::debug!("rate limited, retrying in {:?}", retry_after);
+            tokio::time::sleep(retry_after).await;
+        }
+    }
+
+    async fn read_body(res: reqwest::Response) -> Result<String, GrowattError> {
+        let status = res.status();
+        let body = res.text().await?;
+
+        if utils::is_maintenance_page(status.as_u16(), &body) {
+            return Err(GrowattError::Maintenance {
+                until: utils::maintenance_until(&body),
+            });
+        }
+
+        if !status.is_success() {
+            return Err(GrowattError::Http {
+                status: status.as_u16(),
+                body: utils::snippet(&body, 256).to_owned(),
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn get_url(&self, endpoint: &Endpoint) -> Result<String, GrowattError> {
+        match &self.backend {
+            Backend::WebPortal { server_url } => Ok(format!("{}{}", server_url, endpoint.path())),
+            Backend::OpenApi { base_url, .. } => endpoint
+                .open_api_path()
+                .map(|path| format!("{}{}", base_url, path))
+                .ok_or_else(|| {
+                    GrowattError::InvalidArgument(format!(
+                        "{} is not available with the OpenApi backend",
+                        endpoint.path()
+                    ))
+                }),
+        }
+    }
+
+    /// `data` of an OpenApi response, checking its `error_code`
+    fn open_api_data(content: &str) -> Result<Value, GrowattError> {
+        let mut body: Value = serde_json::from_str(content)?;
+        match body["error_code"].as_i64() {
+            Some(0) => Ok(body["data"].take()),
+            _ => Err(GrowattError::InvalidResponse(format!(
+                "OpenApi error: {}",
+                body["error_msg"].as_str().unwrap_or("unknown")
+            ))),
+        }
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<String, GrowattError> {
+        let endpoint = Endpoint::Login {
+            account: username,
+            password,
+        };
+        let url = self.get_url(&endpoint)?;
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert("User-Agent", header::HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/104.0.0.0 Safari/537.36-11"));
+        headers.insert("Connection", header::HeaderValue::from_static("keep-alive"));
+        self.cookies.clear();
+        if let Some(cookie) = self.cookie_header() {
+            headers.insert(header::COOKIE, cookie);
+        }
+
+        let req = self
+            .with_request_timeout(self.client.post(url))
+            .headers(headers)
+            .form(&endpoint.form());
+        let res = self.send(req).await?;
+
+        log::trace!("login request with status {}", res.status().as_str());
+
+        for el in res.headers().get_all("set-cookie") {
+            let current_cookie = el
+                .to_str()
+                .map_err(|e| GrowattError::InvalidResponse(e.to_string()))?;
+            log::trace!("using cookie {}", current_cookie);
+
+            // Only name=value is sent back, attributes like Path or Secure
+            // are meant for the client
+            let Some((name, value)) = utils::cookie_pair(current_cookie) else {
+                continue;
+            };
+
+            match name {
+                "JSESSIONID" => {
+                    if let Backend::WebPortal { server_url } = &self.backend {
+                        self.referer = format!("{}index;jsessionid={}", server_url, value);
+                    }
+                }
+                "SERVERID" => {}
+                _ => continue,
+            }
+
+            self.cookies.retain(|(n, _)| n != name);
+            self.cookies.push((name.to_owned(), value.to_owned()));
+        }
+
+        let body = Self::read_body(res).await?;
+
+        if !Self::check_res(body.clone()) {
+            Err(GrowattError::InvalidResponse(
+                "Missing success field".to_owned(),
+            ))
+        } else {
+            Ok(body)
+        }
+    }
+
+    pub async fn mix_system_status(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+    ) -> Result<String, GrowattError> {
+        let content = self.mix_status_body(mix_id, plant_id).await?;
+
+        //Strip off unusefull part
+        let v = serde_json::to_string(&self.mix_status_obj(&content)?)?;
+        Ok(v)
+    }
+
+    async fn mix_status_body(&self, mix_id: &str, plant_id: &str) -> Result<String, GrowattError> {
+        self.request(Endpoint::MixStatus {
+            plant_id,
+            mix_sn: mix_id,
+        })
+        .await
+    }
+
+    /// Status payload of a `mix_status_body` response
+    fn mix_status_obj(&self, content: &str) -> Result<Value, GrowattError> {
+        match self.backend {
+            Backend::WebPortal { .. } => Self::extract_obj(content),
+            Backend::OpenApi { .. } => Self::open_api_data(content),
+        }
+    }
+
+    pub async fn mix_total_data(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+    ) -> Result<MixTotal, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::MixTotal {
+                plant_id,
+                mix_sn: mix_id,
+            })
+            .await?;
+        self.parse(obj)
+    }
+
+    /// Raw first page of the device list, `devices` fetches every page
+    pub async fn device_list_by_plant(&self, plant_id: &str) -> Result<String, GrowattError> {
+        let content = self
+            .request(Endpoint::DevicesByPlant { plant_id, page: 1 })
+            .await?;
+        if let Backend::OpenApi { .. } = self.backend {
+            Self::open_api_data(&content)?;
+            return Ok(content);
+        }
+        if !Self::check_res(content.clone()) {
+            Err(GrowattError::InvalidResponse("Succeed false".to_owned()))
+        } else {
+            Ok(content)
+        }
+    }
+
+    pub async fn mix_day_chart(
+        &self,
+        mix_sn: &str,
+        plant_id: &str,
+        date: NaiveDate,
+    ) -> Result<MixDayChart, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::MixEnergyDayChart {
+                plant_id,
+                mix_sn,
+                date,
+            })
+            .await?;
+        self.parse(obj["charts"].clone())
+    }
+
+    /// Raw history records of a MIX for a day, every page is fetched
+    async fn mix_history(
+        &self,
+        mix_sn: &str,
+        plant_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<Value>, GrowattError> {
+        let mut records = Vec::new();
+
+        for page in 0.. {
+            let obj = self
+                .fetch_obj(Endpoint::MixHistory {
+                    plant_id,
+                    mix_sn,
+                    date,
+                    page,
+                })
+                .await?;
+            let datas: Vec<Value> = serde_json::from_value(obj["datas"].clone())?;
+            let have_next = obj["haveNext"].as_bool().unwrap_or(false);
+
+            let last = datas.is_empty() || !have_next;
+            records.extend(datas);
+            if last {
+                break;
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Grid voltage and frequency recorded by a device over a day
+    pub async fn grid_quality_history(
+        &self,
+        device_sn: &str,
+        plant_id: &str,
+        date: NaiveDate,
+    ) -> Result<GridQualitySeries, GrowattError> {
+        #[derive(Deserialize)]
+        struct GridSample {
+            #[serde(deserialize_with = "utils::datetime")]
+            time: NaiveDateTime,
+            #[serde(default, deserialize_with = "utils::option_from_str")]
+            vac1: Option<f32>,
+            #[serde(default, deserialize_with = "utils::option_from_str")]
+            fac: Option<f32>,
+        }
+
+        let mut samples = self
+            .mix_history(device_sn, plant_id, date)
+            .await?
+            .into_iter()
+            .map(|record| self.parse::<GridSample>(record))
+            .collect::<Result<Vec<_>, _>>()?;
+        samples.sort_by_key(|s| s.time);
+
+        let mut series = GridQualitySeries::default();
+        for sample in samples {
+            series.times.push(sample.time);
+            series.voltage.push(sample.vac1);
+            series.frequency.push(sample.fac);
+        }
+
+        Ok(series)
+    }
+
+    /// Samples recorded after `since`, taken from the 5 minutes history.
+    ///
+    /// The portal only filters the history by day, so whole days are fetched
+    /// and older records are dropped here. History times are local to the
+    /// plant, the call fails when its timezone is unknown.
+    pub async fn mix_since(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<MixStatus>, GrowattError> {
+        let offset = self
+            .plant_timezone(plant_id)
+            .await?
+            .ok_or_else(|| GrowattError::InvalidResponse("Unknown plant timezone".to_owned()))?;
+
+        let since = since.with_timezone(&offset);
+        let today = Utc::now().with_timezone(&offset).date_naive();
+
+        let mut samples = Vec::new();
+        for date in since.date_naive().iter_days().take_while(|d| *d <= today) {
+            for mut record in self.mix_history(mix_id, plant_id, date).await? {
+                utils::rename_keys(&mut record, HISTORY_KEYS);
+                let mut status: MixStatus = self.parse(record)?;
+                let time = status
+                    .sample_time
+                    .and_then(|t| offset.from_local_datetime(&t).single())
+                    .ok_or_else(|| {
+                        GrowattError::InvalidResponse("Invalid history time".to_owned())
+                    })?;
+
+                if time > since {
+                    status.when = When::from(time);
+                    samples.push(status);
+                }
+            }
+        }
+
+        samples.sort_by_key(|s| i64::from(s.when));
+        Ok(samples)
+    }
+
+    /// Battery state of health over the given days, as stored by the portal
+    pub async fn soh_history(
+        &self,
+        mix_sn: &str,
+        plant_id: &str,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<(NaiveDate, f32)>, GrowattError> {
+        #[derive(Deserialize)]
+        struct SohSample {
+            #[serde(deserialize_with = "utils::from_str")]
+            date: NaiveDate,
+            #[serde(deserialize_with = "utils::from_str")]
+            soh: f32,
+        }
+
+        let obj = self
+            .fetch_obj(Endpoint::MixSohHistory {
+                plant_id,
+                mix_sn,
+                start: *range.start(),
+                end: *range.end(),
+            })
+            .await?;
+        let samples: Vec<SohSample> = self.parse(obj)?;
+        Ok(samples.into_iter().map(|s| (s.date, s.soh)).collect())
+    }
+
+    pub async fn devices(&self, plant_id: &str) -> Result<Vec<Device>, GrowattError> {
+        let mut devices: Vec<Device> = Vec::new();
+
+        for page in 1.. {
+            let obj = self
+                .fetch_obj(Endpoint::DevicesByPlant { plant_id, page })
+                .await?;
+            let (datas, last): (Vec<Device>, bool) = match self.backend {
+                Backend::WebPortal { .. } => {
+                    let datas: Vec<Device> = self.parse(obj["datas"].clone())?;
+                    let pages = obj["pages"]
+                        .as_u64()
+                        .or_else(|| obj["pages"].as_str()?.parse().ok())
+                        .unwrap_or(1);
+                    let last = datas.is_empty() || u64::from(page) >= pages;
+                    (datas, last)
+                }
+                // Paged by count rather than number of pages
+                Backend::OpenApi { .. } => {
+                    let datas: Vec<Device> = self.parse(obj["devices"].clone())?;
+                    let count = obj["count"].as_u64().unwrap_or(0);
+                    let last = datas.is_empty() || (devices.len() + datas.len()) as u64 >= count;
+                    (datas, last)
+                }
+            };
+
+            devices.extend(datas);
+            if last {
+                break;
+            }
+        }
+
+        for device in devices
+            .iter_mut()
+            .filter(|d| d.device_type == DeviceType::Unknown)
+        {
+            device.device_type = DeviceType::from_serial(&device.serial);
+        }
+
+        Ok(devices)
+    }
+
+    pub async fn device_detail(
+        &self,
+        device_sn: &str,
+        plant_id: &str,
+    ) -> Result<DeviceDetail, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::DeviceInfo {
+                plant_id,
+                device_sn,
+            })
+            .await?;
+        self.parse(obj)
+    }
+
+    /// Catalog of the device models the portal knows about
+    pub async fn device_models(&self) -> Result<Vec<DeviceModel>, GrowattError> {
+        let obj = self.fetch_obj(Endpoint::DeviceTypeList).await?;
+        self.parse(obj)
+    }
+
+    /// Typed MIX status, `when` is the datalogger sample time when the portal
+    /// reports it and the plant timezone is known, the fetch time otherwise.
+    ///
+    /// The timezone comes from the plant info, so the first call for a plant
+    /// also fetches `plant_info` unless its metadata is already cached.
+    pub async fn mix_status(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+    ) -> Result<MixStatus, GrowattError> {
+        let (status, _) = self.mix_status_with_raw(mix_id, plant_id).await?;
+        Ok(status)
+    }
+
+    /// Same as `mix_status`, also returning the response body exactly as
+    /// received so it can be stored and parsed again later
+    pub async fn mix_status_with_raw(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+    ) -> Result<(MixStatus, String), GrowattError> {
+        let content = self.mix_status_body(mix_id, plant_id).await?;
+        let mut status: MixStatus = self.parse(self.mix_status_obj(&content)?)?;
+
+        if let Some(sample_time) = status.sample_time {
+            match self.plant_timezone(plant_id).await {
+                Ok(Some(offset)) => {
+                    if let Some(time) = offset.from_local_datetime(&sample_time).single() {
+                        status.when = When::from(time);
+                    }
+                }
+                Ok(None) => log::warn!("unknown plant timezone, using fetch time"),
+                Err(e) => log::warn!("unable to get plant timezone, using fetch time: {}", e),
+            }
+        }
+
+        Ok((status, content))
+    }
+
+    /// Fetch the MIX status every `interval` until `predicate` holds, giving
+    /// up with `GrowattError::Timeout` once `timeout` has elapsed. A zero
+    /// `interval` would hammer the portal and is an `InvalidArgument`.
+    pub async fn poll_until<F>(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+        interval: Duration,
+        timeout: Duration,
+        predicate: F,
+    ) -> Result<MixStatus, GrowattError>
+    where
+        F: Fn(&MixStatus) -> bool,
+    {
+        if interval.is_zero() {
+            return Err(GrowattError::InvalidArgument(
+                "Polling interval must not be zero".to_owned(),
+            ));
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let next = tokio::time::Instant::now() + interval;
+
+            let status = tokio::time::timeout_at(deadline, self.mix_status(mix_id, plant_id))
+                .await
+                .map_err(|_| GrowattError::Timeout)??;
+            if predicate(&status) {
+                return Ok(status);
+            }
+
+            if next >= deadline {
+                return Err(GrowattError::Timeout);
+            }
+            tokio::time::sleep_until(next).await;
+        }
+    }
+
+    /// Plant info and device list, fetched on first use and then served from
+    /// the cache until `refresh_metadata` is called
+    pub async fn plant_metadata(&self, plant_id: &str) -> Result<PlantMetadata, GrowattError> {
+        if let Some(cached) = self.metadata.lock().unwrap().get(plant_id) {
+            return Ok(cached.clone());
+        }
+
+        let metadata = self.fetch_metadata(plant_id).await?;
+        self.metadata
+            .lock()
+            .unwrap()
+            .insert(plant_id.to_owned(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Offset of the plant timezone, from the cached metadata when there is
+    /// some, otherwise from `plant_info` alone and cached on its own
+    async fn plant_timezone(&self, plant_id: &str) -> Result<Option<FixedOffset>, GrowattError> {
+        if let Some(cached) = self.metadata.lock().unwrap().get(plant_id) {
+            return Ok(cached.info.utc_offset());
+        }
+        if let Some(offset) = self.timezones.lock().unwrap().get(plant_id) {
+            return Ok(*offset);
+        }
+
+        let offset = self.plant_info(plant_id).await?.utc_offset();
+        self.timezones
+            .lock()
+            .unwrap()
+            .insert(plant_id.to_owned(), offset);
+        Ok(offset)
+    }
+
+    async fn fetch_metadata(&self, plant_id: &str) -> Result<PlantMetadata, GrowattError> {
+        Ok(PlantMetadata {
+            info: self.plant_info(plant_id).await?,
+            devices: self.devices(plant_id).await?,
+        })
+    }
+
+    /// Re-fetch the metadata of every cached plant, e.g. after a device has
+    /// been added or a plant renamed, and forget the cached timezones
+    pub async fn refresh_metadata(&mut self) -> Result<(), GrowattError> {
+        let plant_ids: Vec<String> = self.metadata.lock().unwrap().keys().cloned().collect();
+        self.timezones.lock().unwrap().clear();
+
+        for plant_id in plant_ids {
+            let metadata = self.fetch_metadata(&plant_id).await?;
+            self.metadata.lock().unwrap().insert(plant_id, metadata);
+        }
+
+        Ok(())
+    }
+
+    /// Live MIX status along with the device rated power, taken from the
+    /// cached plant metadata. The device list reports it in W, it is
+    /// converted to kW to match the status.
+    pub async fn scaled_status(
+        &self,
+        mix_id: &str,
+        plant_id: &str,
+    ) -> Result<ScaledStatus, GrowattError> {
+        let rated_power = self
+            .plant_metadata(plant_id)
+            .await?
+            .devices
+            .iter()
+            .find(|d| d.serial == mix_id)
+            .and_then(|d| d.nominal_power)
+            .ok_or_else(|| GrowattError::InvalidResponse("Unknown rated power".to_owned()))?;
+
+        let status = self.mix_status(mix_id, plant_id).await?;
+        Ok(ScaledStatus::new(status, rated_power / 1000.0))
+    }
+
+    /// Whether any device of the plant has a battery, from the cached device
+    /// list
+    pub async fn plant_has_storage(&self, plant_id: &str) -> Result<bool, GrowattError> {
+        let devices = self.plant_metadata(plant_id).await?.devices;
+        Ok(devices.iter().any(|d| d.device_type.has_storage()))
+    }
+
+    /// Online/offline/fault tally of the devices of a plant. The portal has
+    /// no count endpoint, so it is taken from the full device list cached by
+    /// `plant_metadata`: the first call fetches every page, later ones are
+    /// as fresh as the last `refresh_metadata`.
+    pub async fn plant_device_health(
+        &self,
+        plant_id: &str,
+    ) -> Result<DeviceHealthCounts, GrowattError> {
+        let devices = self.plant_metadata(plant_id).await?.devices;
+        Ok(DeviceHealthCounts::from_devices(&devices))
+    }
+
+    /// Compact live view of a plant, built from the device list plus one
+    /// status call per online MIX to read its SOC, the only storage type the
+    /// status endpoint serves. The status calls run concurrently, at most
+    /// `max_concurrency` at a time, and a MIX whose status can not be read is
+    /// left out of the SOC.
+    pub async fn plant_live(&self, plant_id: &str) -> Result<PlantLive, GrowattError> {
+        let devices = self.devices(plant_id).await?;
+        let online: Vec<&Device> = devices.iter().filter(|d| d.is_online()).collect();
+
+        let mut live = PlantLive {
+            devices_total: devices.len(),
+            devices_online: online.len(),
+            power: online.iter().map(|d| d.power.unwrap_or_default()).sum(),
+            ..Default::default()
+        };
+
+        let mixes: Vec<&Device> = online
+            .into_iter()
+            .filter(|d| d.device_type == DeviceType::Mix)
+            .collect();
+        let results = self
+            .fan_out(&mixes, |device| self.mix_status(&device.serial, plant_id))
+            .await;
+
+        let mut statuses = Vec::new();
+        for (device, result) in mixes.iter().zip(results) {
+            match result {
+                Ok(status) => statuses.push(status),
+                Err(e) => log::warn!("unable to read the SOC of {}: {}", device.serial, e),
+            }
+        }
+
+        live.soc = MixStatus::aggregate(&statuses).soc;
+        Ok(live)
+    }
+
+    pub async fn plant_info(&self, plant_id: &str) -> Result<PlantInfo, GrowattError> {
+        let obj = self.fetch_obj(Endpoint::PlantData { plant_id }).await?;
+        self.parse(obj)
+    }
+
+    pub async fn plant_generation_vs_expected(
+        &self,
+        plant_id: &str,
+        date: NaiveDate,
+    ) -> Result<GenerationComparison, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::PlantExpectEnergy { plant_id, date })
+            .await?;
+        self.parse(obj)
+    }
+
+    /// Load split by circuit, only available with circuit level monitoring
+    pub async fn circuit_loads(&self, plant_id: &str) -> Result<Vec<CircuitLoad>, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::PlantCircuitLoad { plant_id })
+            .await?;
+        self.parse(obj)
+    }
+
+    /// Self-sufficiency and self-consumption of a plant over the given days,
+    /// from the day charts of its MIX devices. Other device types have no
+    /// such chart, a plant without any MIX is an `InvalidArgument`.
+    pub async fn plant_self_rates(
+        &self,
+        plant_id: &str,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<SelfRates, GrowattError> {
+        let devices = self.plant_metadata(plant_id).await?.devices;
+        let mixes: Vec<&Device> = devices
+            .iter()
+            .filter(|d| d.device_type == DeviceType::Mix)
+            .collect();
+        if mixes.is_empty() {
+            return Err(GrowattError::InvalidArgument(format!(
+                "Plant {} has no MIX device",
+                plant_id
+            )));
+        }
+
+        let (mut pv, mut load, mut import, mut export) = (0.0, 0.0, 0.0, 0.0);
+        for device in mixes {
+            for date in range.start().iter_days().take_while(|d| d <= range.end()) {
+                let chart = self.mix_day_chart(&device.serial, plant_id, date).await?;
+
+                pv += MixDayChart::energy(&chart.photovoltaic);
+                load += MixDayChart::energy(&chart.load);
+                import += MixDayChart::energy(&chart.grid_import);
+                export += MixDayChart::energy(&chart.grid_export);
+            }
+        }
+
+        Ok(SelfRates::from_energies(pv, load, import, export))
+    }
+
+    /// Export everything stored for a plant between `start` and `end` as JSON
+    /// Lines: one `plant` record, one `devices` record and one `faults`
+    /// record with the fault log of the period, then per MIX a `totals`
+    /// record with its lifetime counters as of now, followed by a
+    /// `day_chart` and a `history` record per day. Requests are paced to go
+    /// easy on the portal, see `set_archive_pacing`.
+    pub async fn export_plant_archive<W>(
+        &self,
+        plant_id: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+        mut writer: W,
+    ) -> Result<(), GrowattError>
+    where
+        W: std::io::Write,
+    {
+        #[derive(Serialize)]
+        #[serde(tag = "kind", rename_all = "snake_case")]
+        enum Record<'a> {
+            Plant {
+                plant_id: &'a str,
+                info: &'a PlantInfo,
+            },
+            Devices {
+                devices: &'a [Device],
+            },
+            Faults {
+                events: &'a [FaultEvent],
+            },
+            Totals {
+                device: &'a str,
+                totals: &'a MixTotal,
+            },
+            DayChart {
+                device: &'a str,
+                date: NaiveDate,
+                chart: &'a MixDayChart,
+            },
+            History {
+                device: &'a str,
+                date: NaiveDate,
+                records: &'a [Value],
+            },
+        }
+
+        let mut write = |record: Record| -> Result<(), GrowattError> {
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        };
+
+        let info = self.plant_info(plant_id).await?;
+        write(Record::Plant {
+            plant_id,
+            info: &info,
+        })?;
+
+        let devices = self.devices(plant_id).await?;
+        write(Record::Devices { devices: &devices })?;
+
+        tokio::time::sleep(self.archive_pacing).await;
+        let events = self.plant_faults(plant_id, &(start..=end)).await?;
+        write(Record::Faults { events: &events })?;
+
+        for device in devices.iter().filter(|d| d.device_type == DeviceType::Mix) {
+            tokio::time::sleep(self.archive_pacing).await;
+            let totals = self.mix_total_data(&device.serial, plant_id).await?;
+            write(Record::Totals {
+                device: &device.serial,
+                totals: &totals,
+            })?;
+
+            for date in start.iter_days().take_while(|d| *d <= end) {
+                tokio::time::sleep(self.archive_pacing).await;
+                let chart = self.mix_day_chart(&device.serial, plant_id, date).await?;
+                write(Record::DayChart {
+                    device: &device.serial,
+                    date,
+                    chart: &chart,
+                })?;
+
+                tokio::time::sleep(self.archive_pacing).await;
+                let records = self.mix_history(&device.serial, plant_id, date).await?;
+                write(Record::History {
+                    device: &device.serial,
+                    date,
+                    records: &records,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Energy produced today by each plant, summed over its devices.
+    ///
+    /// Plants are fetched concurrently, at most `max_concurrency` at a time,
+    /// and results come back in the same order as `plant_ids`.
+    pub async fn fleet_energy_today(
+        &self,
+        plant_ids: &[&str],
+    ) -> Vec<Result<(String, f32), GrowattError>> {
+        self.fan_out(plant_ids, |plant_id| self.plant_energy_today(plant_id))
+            .await
+    }
+
+    /// Run `f` on every item with at most `max_concurrency` futures in
+    /// flight, results come back in the same order as `items`
+    async fn fan_out<I, F, Fut>(&self, items: I, f: F) -> Vec<Fut::Output>
+    where
+        I: IntoIterator,
+        F: FnMut(I::Item) -> Fut,
+        Fut: std::future::Future,
+    {
+        // Collected upfront, a lazy iterator here makes the future not `Send`
+        let requests: Vec<Fut> = items.into_iter().map(f).collect();
+
+        stream::iter(requests)
+            .buffered(self.max_concurrency)
+            .collect()
+            .await
+    }
+
+    /// Plants of the logged in account
+    pub async fn plants(&self) -> Result<Vec<Plant>, GrowattError> {
+        let endpoint = Endpoint::PlantList;
+
+        if let Backend::OpenApi { .. } = self.backend {
+            let data = self.fetch_obj(endpoint).await?;
+            return self.parse(data["plants"].clone());
+        }
+
+        // A bare array, without the usual result/obj wrapper
+        let content = self.request(endpoint).await?;
+        self.parse(serde_json::from_str(&content)?)
+    }
+
+    /// Faults logged over the given days by every plant of the account,
+    /// newest first.
+    ///
+    /// Plants are fetched concurrently, at most `max_concurrency` at a time.
+    /// A plant whose log can not be read doesn't stop the others, it is
+    /// reported in `failed_plants`.
+    pub async fn account_faults(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<AccountFaults, GrowattError> {
+        let plants = self.plants().await?;
+
+        let logs = self
+            .fan_out(&plants, |plant| self.plant_faults(&plant.id, &range))
+            .await;
+
+        let mut faults = AccountFaults::default();
+        for (plant, log) in plants.iter().zip(logs) {
+            match log {
+                Ok(events) => faults.events.extend(events),
+                Err(e) => {
+                    log::warn!("unable to read the fault log of plant {}: {}", plant.id, e);
+                    faults.failed_plants.push((plant.id.clone(), e));
+                }
+            }
+        }
+
+        faults
+            .events
+            .sort_by_key(|event| std::cmp::Reverse(event.time));
+        Ok(faults)
+    }
+
+    async fn plant_faults(
+        &self,
+        plant_id: &str,
+        range: &RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<FaultEvent>, GrowattError> {
+        let mut events = Vec::new();
+
+        for page in 1.. {
+            let obj = self
+                .fetch_obj(Endpoint::PlantFaultLog {
+                    plant_id,
+                    start: *range.start(),
+                    end: *range.end(),
+                    page,
+                })
+                .await?;
+            let datas: Vec<FaultEvent> = self.parse(obj["datas"].clone())?;
+            let have_next = obj["haveNext"].as_bool().unwrap_or(false);
+
+            let last = datas.is_empty() || !have_next;
+            events.extend(datas.into_iter().map(|event| FaultEvent {
+                plant_id: plant_id.to_owned(),
+                ..event
+            }));
+            if last {
+                break;
+            }
+        }
+
+        Ok(events)
+    }
+
+    async fn plant_energy_today(&self, plant_id: &str) -> Result<(String, f32), GrowattError> {
+        let devices = self.devices(plant_id).await?;
+        let energy = devices.iter().filter_map(|d| d.energy_today).sum();
+        Ok((plant_id.to_owned(), energy))
+    }
+
+    /// Download the plant photo configured on the portal
+    pub async fn plant_image(&self, plant_id: &str) -> Result<Vec<u8>, GrowattError> {
+        let info = self.plant_info(plant_id).await?;
+        let image_name = info
+            .image_name
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| GrowattError::InvalidResponse("Plant has no image".to_owned()))?;
+
+        let endpoint = Endpoint::PlantImage {
+            image_name: &image_name,
+        };
+
+        let res = self.send(self.get(&endpoint)?).await?;
+
+        log::trace!("plant_image request with status {}", res.status().as_str());
+
+        let status = res.status();
+        if !status.is_success() {
+            return Err(GrowattError::Http {
+                status: status.as_u16(),
+                body: String::new(),
+            });
+        }
+
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    pub async fn datalogger_detail(
+        &self,
+        datalog_sn: &str,
+        plant_id: &str,
+    ) -> Result<DataloggerDetail, GrowattError> {
+        let obj = self.datalog_info(datalog_sn, plant_id).await?;
+        self.parse(obj)
+    }
+
+    /// Firmware running on the datalogger and the latest one known to the
+    /// portal
+    pub async fn datalogger_firmware(
+        &self,
+        datalog_sn: &str,
+        plant_id: &str,
+    ) -> Result<DataloggerFirmware, GrowattError> {
+        let obj = self.datalog_info(datalog_sn, plant_id).await?;
+        self.parse(obj)
+    }
+
+    /// Ask the portal to look for a newer datalogger firmware. The check runs
+    /// in the background, read `datalogger_firmware` again later for the
+    /// result.
+    pub async fn check_datalogger_update(&self, datalog_sn: &str) -> Result<(), GrowattError> {
+        let content = self
+            .request(Endpoint::TcpSet {
+                action: "datalogSet",
+                serial: datalog_sn,
+                setting_type: "upgrade_check",
+                params: &[],
+            })
+            .await?;
+        if !Self::check_success(&content) {
+            Err(GrowattError::InvalidResponse(
+                "Update check rejected".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn datalog_info(&self, datalog_sn: &str, plant_id: &str) -> Result<Value, GrowattError> {
+        self.fetch_obj(Endpoint::DatalogInfo {
+            plant_id,
+            datalog_sn,
+        })
+        .await
+    }
+
+    /// How often the datalogger uploads data, polling faster than this only
+    /// returns the same sample again
+    pub async fn data_interval(
+        &self,
+        datalog_sn: &str,
+        plant_id: &str,
+    ) -> Result<Option<Duration>, GrowattError> {
+        let detail = self.datalogger_detail(datalog_sn, plant_id).await?;
+        Ok(detail.upload_interval())
+    }
+
+    /// Every message of the inbox, every page is fetched
+    pub async fn inbox(&self) -> Result<Vec<InboxMessage>, GrowattError> {
+        let mut messages = Vec::new();
+
+        for page in 1.. {
+            let obj = self.fetch_obj(Endpoint::MessageList { page }).await?;
+            let datas: Vec<InboxMessage> = self.parse(obj["datas"].clone())?;
+            let have_next = obj["haveNext"].as_bool().unwrap_or(false);
+
+            let last = datas.is_empty() || !have_next;
+            messages.extend(datas);
+            if last {
+                break;
+            }
+        }
+
+        Ok(messages)
+    }
+
+    pub async fn mark_message_read(&self, message_id: u64) -> Result<(), GrowattError> {
+        self.fetch_obj(Endpoint::MessageRead { message_id }).await?;
+        Ok(())
+    }
+
+    pub async fn notification_settings(
+        &self,
+        plant_id: &str,
+    ) -> Result<NotificationSettings, GrowattError> {
+        let obj = self
+            .fetch_obj(Endpoint::PlantNoticeSet { plant_id })
+            .await?;
+        self.parse(obj)
+    }
 
-        let res = self
-            .client
-            .post(url)
-            .headers(self.cookie.clone())
-            .headers(hm)
-            .send()
+    pub async fn set_notification_settings(
+        &self,
+        plant_id: &str,
+        settings: &NotificationSettings,
+    ) -> Result<(), GrowattError> {
+        self.fetch_obj(Endpoint::SetPlantNoticeSet { plant_id, settings })
             .await?;
+        Ok(())
+    }
+
+    /// Current setting parameters of a MIX, as a map from the portal keys to
+    /// their values
+    pub async fn mix_settings(
+        &self,
+        mix_sn: &str,
+    ) -> Result<serde_json::Map<String, Value>, GrowattError> {
+        match self.fetch_obj(Endpoint::MixSetParams { mix_sn }).await? {
+            Value::Object(map) => Ok(map),
+            _ => Err(GrowattError::InvalidResponse("Missing settings".to_owned())),
+        }
+    }
+
+    async fn mix_setting<T>(&self, mix_sn: &str, key: &str) -> Result<T, GrowattError>
+    where
+        T: std::str::FromStr,
+    {
+        let settings = self.mix_settings(mix_sn).await?;
+        let value = match settings.get(key) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Number(n)) => n.to_string(),
+            _ => {
+                return Err(GrowattError::InvalidResponse(format!(
+                    "Missing setting {}",
+                    key
+                )))
+            }
+        };
 
-        log::trace!("plant_list request with status {}", res.status().as_str());
+        value
+            .parse()
+            .map_err(|_| GrowattError::InvalidResponse(format!("Invalid setting {}", key)))
+    }
 
-        let content = res.text().await?;
-        if Self::check_res(content.clone()) == false {
-            Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Succeed false").into())
+    /// Write a MIX setting, `setting_type` and `params` are passed as they
+    /// are to the portal (`param1`, `param2`, ...).
+    ///
+    /// The portal forwards the setting to the device before answering and
+    /// returns no job id, so there is no pending write that could be
+    /// cancelled: to revert, write the previous value back.
+    pub async fn update_mix_setting(
+        &self,
+        mix_sn: &str,
+        setting_type: &str,
+        params: &[&str],
+    ) -> Result<(), GrowattError> {
+        let content = self
+            .request(Endpoint::TcpSet {
+                action: "mixSet",
+                serial: mix_sn,
+                setting_type,
+                params,
+            })
+            .await?;
+        if !Self::check_success(&content) {
+            Err(GrowattError::InvalidResponse(format!(
+                "Setting {} rejected",
+                setting_type
+            )))
         } else {
-            Ok(content)
+            Ok(())
+        }
+    }
+
+    /// Active power limit (curtailment) in percent of the rated power
+    pub async fn active_power_limit(&self, mix_sn: &str) -> Result<u8, GrowattError> {
+        self.mix_setting(mix_sn, "activeRate").await
+    }
+
+    pub async fn set_active_power_limit(
+        &self,
+        mix_sn: &str,
+        percent: u8,
+    ) -> Result<(), GrowattError> {
+        if percent > 100 {
+            return Err(GrowattError::InvalidArgument(format!(
+                "Active power limit {}% out of range",
+                percent
+            )));
+        }
+
+        let percent = percent.to_string();
+        self.update_mix_setting(mix_sn, "pv_active_p_rate", &[&percent])
+            .await
+    }
+
+    /// Whether charging the battery from the grid is enabled
+    pub async fn ac_charge_enabled(&self, mix_sn: &str) -> Result<bool, GrowattError> {
+        let enabled: u8 = self.mix_setting(mix_sn, "acChargeEnable").await?;
+        Ok(enabled != 0)
+    }
+
+    /// Toggle only the "charge from grid" flag, leaving the charge schedule
+    /// untouched
+    pub async fn set_ac_charge(&self, mix_sn: &str, enabled: bool) -> Result<(), GrowattError> {
+        let enabled = if enabled { "1" } else { "0" };
+        self.update_mix_setting(mix_sn, "ac_charge", &[enabled])
+            .await
+    }
+
+    /// Backup reserve: the battery is not discharged below this SOC
+    pub async fn reserve_soc(&self, mix_sn: &str) -> Result<u8, GrowattError> {
+        self.mix_setting(mix_sn, "wdisChargeSOCLowLimit").await
+    }
+
+    pub async fn set_reserve_soc(&self, mix_sn: &str, soc: u8) -> Result<(), GrowattError> {
+        if soc > 100 {
+            return Err(GrowattError::InvalidArgument(format!(
+                "Reserve SOC {}% out of range",
+                soc
+            )));
+        }
+
+        let soc = soc.to_string();
+        self.update_mix_setting(mix_sn, "discharge_stop_soc", &[&soc])
+            .await
+    }
+
+    /// Peak shaving threshold in kW: the battery discharges to keep the grid
+    /// import below it
+    pub async fn peak_shaving_limit(&self, mix_sn: &str) -> Result<f32, GrowattError> {
+        self.mix_setting(mix_sn, "peakShavingPower").await
+    }
+
+    pub async fn set_peak_shaving_limit(&self, mix_sn: &str, kw: f32) -> Result<(), GrowattError> {
+        if !kw.is_finite() || kw < 0.0 {
+            return Err(GrowattError::InvalidArgument(format!(
+                "Peak shaving limit {} kW out of range",
+                kw
+            )));
         }
+
+        let kw = kw.to_string();
+        self.update_mix_setting(mix_sn, "peak_shaving", &[&kw])
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    /// Local HTTP server answering each request with the raw response
+    /// `respond` builds from its request line. Returns the server url.
+    fn scripted_server(mut respond: impl FnMut(&str) -> String + Send + 'static) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                // Read the whole request before answering
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while let Ok(n @ 1..) = stream.read(&mut buf) {
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break;
+                        }
+                    }
+                }
+
+                let request = String::from_utf8_lossy(&request);
+                let line = request.lines().next().unwrap_or_default();
+                let _ = stream.write_all(respond(line).as_bytes());
+            }
+        });
+
+        url
+    }
+
+    fn http_response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    /// Local HTTP server answering each request with the body of the first
+    /// route found in its request line, 404 when none matches. Returns the
+    /// server url.
+    fn fixture_server(routes: Vec<(&'static str, &'static str)>) -> String {
+        scripted_server(move |line| {
+            routes
+                .iter()
+                .find(|(route, _)| line.contains(route))
+                .map_or_else(
+                    || http_response("404 Not Found", "", ""),
+                    |(_, body)| http_response("200 OK", "", body),
+                )
+        })
+    }
+
+    fn fixture_client(routes: Vec<(&'static str, &'static str)>) -> GrowattServer {
+        GrowattServer::with_backend(Backend::WebPortal {
+            server_url: fixture_server(routes),
+        })
+    }
+
     #[actix_rt::test]
     async fn login() {
         let username = std::env::var("GROWATT_TESTS_USERNAME").unwrap();
@@ -261,6 +3313,7 @@ mod tests {
     }
 
     #[actix_rt::test]
+    #[allow(clippy::bool_assert_comparison)]
     async fn login_wrong_credential() {
         let username = "one".to_owned();
         let password = "two".to_owned();
@@ -270,6 +3323,7 @@ mod tests {
     }
 
     #[actix_rt::test]
+    #[allow(unused_variables, clippy::bool_assert_comparison)]
     async fn get_mix_data() {
         let username = std::env::var("GROWATT_TESTS_USERNAME").unwrap();
         let password = std::env::var("GROWATT_TESTS_PASSWORD").unwrap();
@@ -281,11 +3335,921 @@ mod tests {
 
         let res = client.device_list_by_plant(&plant_id).await;
 
-
-        let res = client
-            .mix_system_status(&mix_id, &plant_id)
-            .await;
+        let res = client.mix_system_status(&mix_id, &plant_id).await;
 
         assert_eq!(res.is_ok(), true);
     }
+
+    #[test]
+    fn parse_notification_settings() {
+        let obj = r#"{"faultNotice":"1","warnNotice":"0","offlineNotice":"1","noticeEmail":"me@example.com"}"#;
+        let settings: NotificationSettings = serde_json::from_str(obj).unwrap();
+
+        assert!(settings.fault);
+        assert!(!settings.warning);
+        assert!(settings.offline);
+        assert_eq!(settings.email, "me@example.com");
+    }
+
+    #[test]
+    fn energy_accumulator_trapezoid() {
+        let hour = 3_600_000_000_000;
+        let mut acc = EnergyAccumulator::new();
+
+        let first = MixStatus {
+            when: When(0),
+            power_from_photovoltaic_1: 1.0,
+            power_to_grid: 0.25,
+            ..Default::default()
+        };
+        let second = MixStatus {
+            when: When(hour),
+            power_from_photovoltaic_1: 2.0,
+            power_from_photovoltaic_2: Some(1.0),
+            power_to_grid: 0.75,
+            ..Default::default()
+        };
+
+        acc.push(&first);
+        acc.push(&second);
+        // a stale sample must not be integrated
+        acc.push(&first);
+
+        assert_eq!(acc.photovoltaic, 2.0);
+        assert_eq!(acc.grid_export, 0.5);
+        assert_eq!(acc.load, 0.0);
+    }
+
+    #[test]
+    fn http_error_body_snippet() {
+        let body = "è".repeat(300);
+        assert_eq!(utils::snippet(&body, 256).chars().count(), 256);
+        assert_eq!(utils::snippet("short", 256), "short");
+
+        let err = GrowattError::Http {
+            status: 503,
+            body: "Service Unavailable".to_owned(),
+        };
+        assert_eq!(err.to_string(), "http status 503: Service Unavailable");
+    }
+
+    #[test]
+    fn parse_device_list() {
+        let obj = r#"[
+            {"sn":"AAA1234567","deviceType":"mix","alias":"MIX","datalogSn":"DL1","status":"1","pac":"1200.5","eToday":"7.2","nominalPower":"3600"},
+            {"sn":"BBB1234567","deviceType":"foo","status":"-1","pac":""}
+        ]"#;
+        let devices: Vec<Device> = serde_json::from_str(obj).unwrap();
+
+        assert_eq!(devices[0].device_type, DeviceType::Mix);
+        assert!(devices[0].is_online());
+        assert_eq!(devices[0].power, Some(1200.5));
+        assert_eq!(devices[1].device_type, DeviceType::Unknown);
+        assert!(!devices[1].is_online());
+        assert_eq!(devices[1].power, None);
+    }
+
+    #[test]
+    fn fingerprint_ignores_values() {
+        let a: Value =
+            serde_json::from_str(r#"{"SOC":"50","pPv1":"1.0","list":[{"x":1}]}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"pPv1":"2.0","SOC":"90","list":[]}"#).unwrap();
+        let c: Value =
+            serde_json::from_str(r#"{"soc":"50","pPv1":"1.0","list":[{"x":1}]}"#).unwrap();
+
+        let b_full: Value =
+            serde_json::from_str(r#"{"pPv1":"2.0","SOC":"90","list":[{"x":7}]}"#).unwrap();
+
+        assert_ne!(response_fingerprint(&a), response_fingerprint(&b));
+        assert_eq!(response_fingerprint(&a), response_fingerprint(&b_full));
+        assert_ne!(response_fingerprint(&a), response_fingerprint(&c));
+    }
+
+    #[test]
+    fn performance_ratio() {
+        let day = GenerationComparison {
+            actual: 8.0,
+            expected: Some(10.0),
+        };
+        assert_eq!(day.performance_ratio(), Some(0.8));
+
+        let unknown = GenerationComparison {
+            actual: 8.0,
+            expected: Some(0.0),
+        };
+        assert_eq!(unknown.performance_ratio(), None);
+    }
+
+    #[test]
+    fn retry_after_header() {
+        assert_eq!(
+            utils::parse_retry_after("120"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            utils::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(utils::parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn endpoint_path_and_query() {
+        let endpoint = Endpoint::DevicesByPlant {
+            plant_id: "42",
+            page: 1,
+        };
+        assert_eq!(endpoint.path(), "panel/getDevicesByPlantList");
+        assert_eq!(
+            endpoint.query(),
+            vec![("plantId", "42".to_owned()), ("currPage", "1".to_owned())]
+        );
+        assert!(endpoint.form().is_empty());
+
+        let endpoint = Endpoint::MixEnergyDayChart {
+            plant_id: "42",
+            mix_sn: "MIX1234567",
+            date: NaiveDate::from_ymd_opt(2023, 5, 2).unwrap(),
+        };
+        assert_eq!(endpoint.query(), vec![("plantId", "42".to_owned())]);
+        assert_eq!(
+            endpoint.form(),
+            vec![
+                ("mixSn".to_owned(), "MIX1234567".to_owned()),
+                ("date".to_owned(), "2023-05-02".to_owned())
+            ]
+        );
+
+        let endpoint = Endpoint::TcpSet {
+            action: "mixSet",
+            serial: "MIX1234567",
+            setting_type: "ac_charge",
+            params: &["1"],
+        };
+        assert!(endpoint.query().is_empty());
+        assert_eq!(endpoint.form()[3], ("param1".to_owned(), "1".to_owned()));
+    }
+
+    /// Minimal MixStatus payload with every required field zeroed.
+    const MIX_STATUS: &str = r#"{"chargePower":0,"SOC":50,"pLocalLoad":0,"pPv1":0,"pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":0,"vBat":0,"vPv1":0}"#;
+
+    fn mix_status_json(overrides: Value) -> Value {
+        let mut obj: Value = serde_json::from_str(MIX_STATUS).unwrap();
+        if let (Some(obj), Value::Object(overrides)) = (obj.as_object_mut(), overrides) {
+            obj.extend(overrides);
+        }
+        obj
+    }
+
+    #[test]
+    fn parse_quoted_and_plain_numbers() {
+        let quoted = r#"{"chargePower":"0","SOC":"87","pLocalLoad":"450.5","pPv1":"3.4","pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":"230.1","vBat":"52.3","vPv1":"310","iso":"1200"}"#;
+        let plain = r#"{"chargePower":0,"SOC":87,"pLocalLoad":450.5,"pPv1":3.4,"pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":230.1,"vBat":52.3,"vPv1":310,"iso":1200}"#;
+
+        let quoted: MixStatus = serde_json::from_str(quoted).unwrap();
+        let plain: MixStatus = serde_json::from_str(plain).unwrap();
+
+        assert_eq!(quoted.soc, 87);
+        assert_eq!(plain.soc, 87);
+        assert_eq!(quoted.power_from_photovoltaic_1, 3.4);
+        assert_eq!(plain.power_from_photovoltaic_1, 3.4);
+        assert_eq!(plain.insulation_resistance, Some(1200.0));
+    }
+
+    #[test]
+    fn day_chart_summary() {
+        let obj = r#"{"ppv":[null,"",0,"1200",3600,"2400"],"userLoad":["300"]}"#;
+        let chart: MixDayChart = serde_json::from_str(obj).unwrap();
+        let summary = chart.summary();
+
+        assert_eq!(chart.photovoltaic[0], None);
+        assert_eq!(chart.grid_export, vec![]);
+        assert_eq!(summary.peak_photovoltaic, 3600.0);
+        assert_eq!(summary.peak_time, NaiveTime::from_hms_opt(0, 20, 0));
+        assert_eq!(summary.photovoltaic_energy, 600.0);
+    }
+
+    #[test]
+    fn extract_double_encoded_obj() {
+        let plain = r#"{"result":1,"obj":{"SOC":"50"}}"#;
+        let double = r#"{"result":1,"obj":"{\"SOC\":\"50\"}"}"#;
+        let text = r#"{"result":1,"obj":"just text"}"#;
+
+        let expected: Value = serde_json::from_str(r#"{"SOC":"50"}"#).unwrap();
+        assert_eq!(GrowattServer::extract_obj(plain).unwrap(), expected);
+        assert_eq!(GrowattServer::extract_obj(double).unwrap(), expected);
+        assert_eq!(
+            GrowattServer::extract_obj(text).unwrap(),
+            Value::String("just text".to_owned())
+        );
+    }
+
+    #[test]
+    fn plant_utc_offset() {
+        let mut info = PlantInfo {
+            timezone: "8".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(info.utc_offset(), FixedOffset::east_opt(8 * 3600));
+
+        info.timezone = "GMT-5.5".to_owned();
+        assert_eq!(info.utc_offset(), FixedOffset::west_opt(5 * 3600 + 1800));
+
+        info.timezone = String::new();
+        assert_eq!(info.utc_offset(), None);
+    }
+
+    #[test]
+    fn parse_sample_time() {
+        let obj = mix_status_json(serde_json::json!({"lastUpdateTime": "2023-09-04 10:45:00"}));
+        let status: MixStatus = serde_json::from_value(obj).unwrap();
+
+        assert_eq!(
+            status.sample_time,
+            NaiveDate::from_ymd_opt(2023, 9, 4).and_then(|d| d.and_hms_opt(10, 45, 0))
+        );
+    }
+
+    #[test]
+    fn device_type_from_serial() {
+        assert_eq!(DeviceType::from_serial("MIX1234567"), DeviceType::Mix);
+        assert_eq!(DeviceType::from_serial(" min0a12345"), DeviceType::Tlx);
+        assert_eq!(DeviceType::from_serial("XYZ"), DeviceType::Unknown);
+        assert!(DeviceType::from_serial("SPH6000").has_storage());
+    }
+
+    #[test]
+    fn self_rates() {
+        let rates = SelfRates::from_energies(10.0, 8.0, 2.0, 4.0);
+        assert_eq!(rates.self_sufficiency, Some(0.75));
+        assert_eq!(rates.self_consumption, Some(0.6));
+
+        let night = SelfRates::from_energies(0.0, 1.0, 1.0, 0.0);
+        assert_eq!(night.self_sufficiency, Some(0.0));
+        assert_eq!(night.self_consumption, None);
+    }
+
+    #[test]
+    fn parse_mix_total() {
+        let obj =
+            r#"{"epvToday":"12.4","eToUserToday":"3.1","eToGridToday":"5.0","eToGridTotal":""}"#;
+        let total: MixTotal = serde_json::from_str(obj).unwrap();
+
+        assert_eq!(total.import_today, Some(3.1));
+        assert_eq!(total.export_today, Some(5.0));
+        assert_eq!(total.export_total, None);
+    }
+
+    #[test]
+    fn maintenance_page() {
+        let page = "<html><head><title>System Maintenance</title></head><body>System maintenance from 2023-09-04 01:00 to 2023-09-04 05:30, sorry</body></html>";
+        let login = r#"<html><head><title>Login</title></head><body><form action="login"></form><footer><a href="/notice">Maintenance schedule</a></footer></body></html>"#;
+
+        assert!(utils::is_maintenance_page(200, page));
+        assert!(utils::is_maintenance_page(
+            503,
+            "<html><body>Service Unavailable</body></html>"
+        ));
+        assert!(!utils::is_maintenance_page(200, login));
+        assert!(!utils::is_maintenance_page(
+            503,
+            r#"{"result":1,"msg":"maintenance"}"#
+        ));
+        assert_eq!(
+            utils::maintenance_until(page),
+            NaiveDate::from_ymd_opt(2023, 9, 4).and_then(|d| d.and_hms_opt(5, 30, 0))
+        );
+    }
+
+    #[test]
+    fn aggregate_weighted_soc() {
+        let small = MixStatus {
+            soc: 100,
+            power_from_photovoltaic_1: 1.0,
+            ..Default::default()
+        };
+        let big = MixStatus {
+            soc: 40,
+            power_from_photovoltaic_1: 0.5,
+            power_from_photovoltaic_2: Some(0.25),
+            ..Default::default()
+        };
+
+        let plain = MixStatus::aggregate(&[small, big]);
+        assert_eq!(plain.photovoltaic, 1.75);
+        assert_eq!(plain.soc, Some(70.0));
+
+        let weighted = MixStatus::aggregate_weighted(&[(small, 5.0), (big, 15.0)]);
+        assert_eq!(weighted.soc, Some(55.0));
+        assert_eq!(MixStatus::aggregate(&[]).soc, None);
+    }
+
+    #[test]
+    fn device_health_counts() {
+        let device = |status| Device {
+            status,
+            ..Default::default()
+        };
+        let counts =
+            DeviceHealthCounts::from_devices(&[device(1), device(0), device(-1), device(3)]);
+
+        assert_eq!(counts.online, 2);
+        assert_eq!(counts.offline, 1);
+        assert_eq!(counts.fault, 1);
+        assert_eq!(counts.total(), 4);
+    }
+
+    #[test]
+    fn session_cookie_header() {
+        assert_eq!(
+            utils::cookie_pair("JSESSIONID=ABC123; Path=/; Secure; HttpOnly; SameSite=None"),
+            Some(("JSESSIONID", "ABC123"))
+        );
+        assert_eq!(utils::cookie_pair("Secure"), None);
+
+        let mut client = GrowattServer::new();
+        client.cookies = vec![
+            ("JSESSIONID".to_owned(), "ABC123".to_owned()),
+            ("SERVERID".to_owned(), "xyz|1".to_owned()),
+        ];
+        client.set_language("en");
+        assert_eq!(
+            client.cookie_header().unwrap(),
+            "JSESSIONID=ABC123; SERVERID=xyz|1; lang=en"
+        );
+    }
+
+    #[test]
+    fn portal_date_formats() {
+        let date = NaiveDate::from_ymd_opt(2023, 9, 4).unwrap();
+        assert_eq!(utils::format_date(date), "2023-09-04");
+
+        let datetime = NaiveDateTime::parse_from_str("2023-09-04 07:05:30", utils::DATETIME_FORMAT);
+        assert_eq!(datetime.map(|d| d.date()), Ok(date));
+    }
+
+    #[test]
+    fn stale_samples() {
+        let at = |min| MixStatus {
+            sample_time: NaiveDate::from_ymd_opt(2023, 9, 4)
+                .and_then(|d| d.and_hms_opt(10, min, 0)),
+            ..Default::default()
+        };
+        let mut filter = StaleSampleFilter::new();
+
+        assert!(!filter.is_stale(&at(0)));
+        assert!(filter.is_stale(&at(0)));
+        assert!(!filter.is_stale(&at(5)));
+        assert!(!filter.is_stale(&MixStatus::default()));
+        assert!(at(5).is_same_sample(&at(5)));
+        assert!(!MixStatus::default().is_same_sample(&MixStatus::default()));
+    }
+
+    #[test]
+    fn scaled_status_percentages() {
+        let status = MixStatus {
+            power_from_photovoltaic_1: 1.8,
+            power_to_load: 0.9,
+            ..Default::default()
+        };
+
+        let scaled = ScaledStatus::new(status, 3.6);
+        assert_eq!(scaled.pv_percent(), Some(50.0));
+        assert_eq!(scaled.load_percent(), Some(25.0));
+        assert_eq!(ScaledStatus::new(status, 0.0).pv_percent(), None);
+    }
+
+    #[actix_rt::test]
+    async fn scaled_status_units() {
+        let client = fixture_client(vec![
+            (
+                "panel/getPlantData",
+                r#"{"result":1,"obj":{"plantName":"Home"}}"#,
+            ),
+            (
+                "panel/getDevicesByPlantList",
+                r#"{"result":1,"obj":{"datas":[{"sn":"MIX1234567","nominalPower":"3600"}],"pages":1}}"#,
+            ),
+            (
+                "panel/mix/getMIXStatusData",
+                r#"{"result":1,"obj":{"chargePower":0,"SOC":50,"pLocalLoad":"0.9","pPv1":"1.8","pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":0,"vBat":0,"vPv1":0}}"#,
+            ),
+        ]);
+
+        let scaled = client.scaled_status("MIX1234567", "1").await.unwrap();
+        assert_eq!(scaled.rated_power, 3.6);
+        assert_eq!(scaled.pv_percent(), Some(50.0));
+    }
+
+    #[test]
+    fn lenient_parse_mode() {
+        let obj = mix_status_json(serde_json::json!({
+            "chargePower": "n/a",
+            "SOC": "120",
+            "pPv1": null,
+            "vBat": {"value": 52.3},
+            "iso": "--",
+            "lastUpdateTime": "yesterday",
+        }));
+        let mut client = GrowattServer::new();
+
+        assert!(client.parse::<MixStatus>(obj.clone()).is_err());
+
+        client.set_parse_mode(ParseMode::Lenient);
+        let lenient: MixStatus = client.parse(obj).unwrap();
+        assert_eq!(lenient.power_battery_charge, 0.0);
+        assert_eq!(lenient.soc, 100);
+        assert_eq!(lenient.power_from_photovoltaic_1, 0.0);
+        assert_eq!(lenient.voltage_battery, 0.0);
+        assert_eq!(lenient.insulation_resistance, None);
+        assert_eq!(lenient.sample_time, None);
+    }
+
+    #[test]
+    fn malformed_diagnostics() {
+        let obj = mix_status_json(serde_json::json!({
+            "iso": "--",
+            "antiBackflowStatus": true,
+            "bmsProtectStatus": "-1",
+        }));
+        let mut client = GrowattServer::new();
+
+        let status: MixStatus = client.parse(obj.clone()).unwrap();
+        assert_eq!(status.insulation_resistance, None);
+        assert_eq!(status.anti_backflow_status, None);
+        assert_eq!(status.battery_protection_status, None);
+
+        client.set_parse_mode(ParseMode::Strict);
+        assert!(client.parse::<MixStatus>(obj).is_err());
+    }
+
+    #[test]
+    fn strict_parse_mode() {
+        let obj = mix_status_json(serde_json::json!({"vPv1": "310", "iso": null}));
+        let mut client = GrowattServer::new();
+        client.set_parse_mode(ParseMode::Strict);
+
+        let status: MixStatus = client.parse(obj.clone()).unwrap();
+        assert_eq!(status.voltage_photovoltaic_1, 310.0);
+
+        let mut unknown = obj;
+        unknown["fwVersion"] = Value::from("GH1.0");
+        let err = client.parse::<MixStatus>(unknown).unwrap_err();
+        assert!(err.to_string().contains("unknown field `fwVersion`"));
+
+        let devices = serde_json::json!([{"sn": "MIX1234567", "deviceType": "mix", "extra": 1}]);
+        assert!(client.parse::<Vec<Device>>(devices.clone()).is_err());
+        client.set_parse_mode(ParseMode::Standard);
+        assert!(client.parse::<Vec<Device>>(devices).is_ok());
+    }
+
+    #[test]
+    fn status_with_both_spellings() {
+        let obj = mix_status_json(serde_json::json!({"vAc1": "230.1", "vac1": "230.1"}));
+        let status: MixStatus = serde_json::from_value(obj).unwrap();
+        assert_eq!(status.voltage_grid, 230.1);
+    }
+
+    #[test]
+    fn history_record_keys() {
+        let mut record = serde_json::json!({
+            "pcharge1": 0, "soc": "64", "pLocalLoad1": 0, "ppv1": "1.2",
+            "pacToGridTotal": 0, "pacToUserTotal": 0, "pdischarge1": 0,
+            "vac1": "231", "vAc1": "230", "vbat": 0, "vpv1": 0,
+            "time": "2023-09-04 10:45:00",
+        });
+        utils::rename_keys(&mut record, HISTORY_KEYS);
+        let status: MixStatus = serde_json::from_value(record).unwrap();
+
+        assert_eq!(status.soc, 64);
+        assert_eq!(status.power_from_photovoltaic_1, 1.2);
+        assert_eq!(status.voltage_grid, 230.0);
+        assert!(status.sample_time.is_some());
+    }
+
+    #[test]
+    fn cycle_count() {
+        let obj = mix_status_json(serde_json::json!({"bmsCycleCount": "412"}));
+        let status: MixStatus = serde_json::from_value(obj).unwrap();
+        assert_eq!(status.cycle_count, Some(412));
+    }
+
+    #[test]
+    fn device_model_catalog() {
+        let models: Vec<DeviceModel> = serde_json::from_str(
+            r#"[{"modelCode":"A0B0D0T0PFU1M3S4","modelName":"SPH 5000TL3 BH","deviceType":"sph","nominalPower":"5000"},{"modelCode":"X9","deviceType":"new"}]"#,
+        )
+        .unwrap();
+        assert_eq!(models[0].name, "SPH 5000TL3 BH");
+        assert!(models[0].device_type.has_storage());
+        assert_eq!(models[1].device_type, DeviceType::Unknown);
+    }
+
+    #[test]
+    fn warning_mask() {
+        assert_eq!(
+            Warning::from_mask(0b1000_0011),
+            vec![Warning::Fan, Warning::Communication, Warning::Other(7)]
+        );
+        assert!(MixStatus::default().warnings().is_empty());
+    }
+
+    #[test]
+    fn fault_event() {
+        let event: FaultEvent = serde_json::from_str(
+            r#"{"deviceSn":"MIX1234567","eventId":102,"eventName":"Fan warning","time":"2023-05-02 13:05:00"}"#,
+        )
+        .unwrap();
+        assert_eq!(event.code, "102");
+        assert!(event.plant_id.is_empty());
+        assert_eq!(
+            event.time,
+            NaiveDate::from_ymd_opt(2023, 5, 2)
+                .unwrap()
+                .and_hms_opt(13, 5, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn datalogger_update_available() {
+        let mut firmware = DataloggerFirmware {
+            serial: "DDD0A12345".to_owned(),
+            version: "3.1.0.0".to_owned(),
+            latest_version: None,
+        };
+        assert!(!firmware.update_available());
+
+        firmware.latest_version = Some("3.1.0.0".to_owned());
+        assert!(!firmware.update_available());
+
+        firmware.latest_version = Some("3.2.0.1".to_owned());
+        assert!(firmware.update_available());
+    }
+
+    #[test]
+    fn open_api_backend() {
+        let client = GrowattServer::with_backend(Backend::OpenApi {
+            token: "secret".to_owned(),
+            base_url: "https://openapi.growatt.com/".to_owned(),
+        });
+        assert_eq!(
+            client.get_url(&Endpoint::PlantList).unwrap(),
+            "https://openapi.growatt.com/v1/plant/list"
+        );
+        assert!(client
+            .get_url(&Endpoint::MixTotal {
+                plant_id: "1",
+                mix_sn: "MIX1234567",
+            })
+            .is_err());
+        assert_eq!(client.session_headers()["token"], "secret");
+        assert!(!format!("{:?}", client.backend).contains("secret"));
+
+        let data = GrowattServer::open_api_data(
+            r#"{"error_code":0,"error_msg":"","data":{"count":1,"plants":[{"plant_id":42,"name":"Home"}]}}"#,
+        )
+        .unwrap();
+        let plants: Vec<Plant> = serde_json::from_value(data["plants"].clone()).unwrap();
+        assert_eq!(plants[0].id, "42");
+        assert_eq!(plants[0].name, "Home");
+        assert!(GrowattServer::open_api_data(
+            r#"{"error_code":10011,"error_msg":"error_permission_denied"}"#
+        )
+        .is_err());
+    }
+
+    #[actix_rt::test]
+    async fn open_api_dispatch() {
+        let client = GrowattServer::with_backend(Backend::OpenApi {
+            token: "secret".to_owned(),
+            base_url: fixture_server(vec![
+                (
+                    "GET /v1/plant/details?plant_id=1 ",
+                    r#"{"error_code":0,"data":{"name":"Home","peak_power":"5.2","timezone":"1"}}"#,
+                ),
+                (
+                    "GET /v1/device/list?plant_id=1&page=1 ",
+                    r#"{"error_code":0,"data":{"count":2,"devices":[{"device_sn":"MIX1234567","datalogger_sn":"DDD0A12345"}]}}"#,
+                ),
+                (
+                    "GET /v1/device/list?plant_id=1&page=2 ",
+                    r#"{"error_code":0,"data":{"count":2,"devices":[{"device_sn":"MIX7654321"}]}}"#,
+                ),
+                (
+                    "GET /v1/device/mix/mix_last_data?mix_sn=MIX1234567 ",
+                    r#"{"error_code":0,"data":{"chargePower":0,"SOC":64,"pLocalLoad":0,"pPv1":0,"pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":0,"vBat":0,"vPv1":0}}"#,
+                ),
+            ]),
+        });
+
+        let info = client.plant_info("1").await.unwrap();
+        assert_eq!(info.name, "Home");
+        assert_eq!(info.nominal_power, Some(5.2));
+
+        let devices = client.devices("1").await.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].serial, "MIX1234567");
+        assert_eq!(devices[0].datalog_sn, "DDD0A12345");
+        assert_eq!(devices[1].device_type, DeviceType::Mix);
+
+        let status = client.mix_status("MIX1234567", "1").await.unwrap();
+        assert_eq!(status.soc, 64);
+
+        assert!(matches!(
+            client.inbox().await,
+            Err(GrowattError::InvalidArgument(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn rate_limit_retries() {
+        let plant_data = r#"{"result":1,"obj":{"plantName":"Home"}}"#;
+        // Rate limited on every other request
+        let mut requests = 0;
+        let mut client = GrowattServer::with_backend(Backend::WebPortal {
+            server_url: scripted_server(move |_| {
+                requests += 1;
+                match requests % 2 {
+                    1 => http_response("429 Too Many Requests", "Retry-After: 0\r\n", ""),
+                    _ => http_response("200 OK", "", plant_data),
+                }
+            }),
+        });
+
+        assert!(matches!(
+            client.plant_info("1").await,
+            Err(GrowattError::RateLimited { retry_after }) if retry_after.is_zero()
+        ));
+        // Served the 200, the next request is rate limited again
+        client.plant_info("1").await.unwrap();
+
+        client.set_max_retries(1);
+        assert_eq!(client.plant_info("1").await.unwrap().name, "Home");
+    }
+
+    #[actix_rt::test]
+    async fn poll_until_outcomes() {
+        // SOC rises by 10 on every status read, up to 100
+        let mut soc = 0;
+        let client = GrowattServer::with_backend(Backend::WebPortal {
+            server_url: scripted_server(move |_| {
+                soc = (soc + 10).min(100);
+                let obj = mix_status_json(serde_json::json!({"SOC": soc}));
+                http_response("200 OK", "", &format!(r#"{{"result":1,"obj":{}}}"#, obj))
+            }),
+        });
+        let poll = |interval, timeout, target: u32| {
+            client.poll_until("MIX1234567", "1", interval, timeout, move |s| {
+                s.soc >= target
+            })
+        };
+
+        let status = poll(Duration::from_millis(1), Duration::from_secs(5), 30)
+            .await
+            .unwrap();
+        assert_eq!(status.soc, 30);
+
+        assert!(matches!(
+            poll(Duration::from_millis(10), Duration::from_millis(50), 101).await,
+            Err(GrowattError::Timeout)
+        ));
+        assert!(matches!(
+            poll(Duration::ZERO, Duration::from_millis(50), 101).await,
+            Err(GrowattError::InvalidArgument(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn request_timeout() {
+        // Never accepted, the request hangs until the timeout expires
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = GrowattServer::with_backend(Backend::WebPortal {
+            server_url: format!("http://{}/", listener.local_addr().unwrap()),
+        });
+        client.set_timeout(Duration::from_millis(50));
+
+        let res = client.plant_info("1").await;
+        assert!(matches!(res, Err(GrowattError::Timeout)));
+    }
+
+    #[actix_rt::test]
+    async fn plant_list_bare_array() {
+        let client = fixture_client(vec![(
+            "index/getPlantListTitle",
+            r#"[{"id":"1234567","timezone":"8","plantName":"Home","isShare":"false"},{"id":7654321,"plantName":"Barn"}]"#,
+        )]);
+
+        let plants = client.plants().await.unwrap();
+        assert_eq!(plants.len(), 2);
+        assert_eq!(plants[0].id, "1234567");
+        assert_eq!(plants[0].name, "Home");
+        assert_eq!(plants[1].id, "7654321");
+    }
+
+    #[actix_rt::test]
+    async fn account_faults_isolate_failing_plants() {
+        let client = fixture_client(vec![
+            (
+                "index/getPlantListTitle",
+                r#"[{"id":"1","plantName":"A"},{"id":"2","plantName":"B"}]"#,
+            ),
+            (
+                "log/getNewPlantFaultLog?plantId=1",
+                r#"{"result":1,"obj":{"datas":[{"deviceSn":"MIX1234567","eventId":"102","eventName":"Fan warning","time":"2023-05-02 13:05:00"}],"haveNext":false}}"#,
+            ),
+        ]);
+
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        let faults = client.account_faults(day..=day).await.unwrap();
+
+        assert_eq!(faults.events.len(), 1);
+        assert_eq!(faults.events[0].plant_id, "1");
+        assert_eq!(faults.failed_plants.len(), 1);
+        assert_eq!(faults.failed_plants[0].0, "2");
+        assert!(matches!(
+            faults.failed_plants[0].1,
+            GrowattError::Http { status: 404, .. }
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn fault_log_stops_on_empty_page() {
+        let client = fixture_client(vec![(
+            "log/getNewPlantFaultLog",
+            r#"{"result":1,"obj":{"datas":[],"haveNext":true}}"#,
+        )]);
+
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        let events = client.plant_faults("1", &(day..=day)).await.unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn mix_history_stops_on_empty_page() {
+        let client = fixture_client(vec![(
+            "device/getMIXHistory",
+            r#"{"result":1,"obj":{"datas":[],"haveNext":true}}"#,
+        )]);
+
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        let records = client.mix_history("MIX1234567", "1", day).await.unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn self_rates_need_a_mix() {
+        let client = fixture_client(vec![
+            (
+                "panel/getPlantData",
+                r#"{"result":1,"obj":{"plantName":"Home"}}"#,
+            ),
+            (
+                "panel/getDevicesByPlantList",
+                r#"{"result":1,"obj":{"datas":[{"sn":"SPH1234567","deviceType":"sph"}],"pages":1}}"#,
+            ),
+        ]);
+
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        assert!(matches!(
+            client.plant_self_rates("1", day..=day).await,
+            Err(GrowattError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn fan_out_futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let client = GrowattServer::new();
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        assert_send(&client.fleet_energy_today(&["1"]));
+        assert_send(&client.account_faults(day..=day));
+        assert_send(&client.plant_live("1"));
+    }
+
+    #[actix_rt::test]
+    async fn mix_status_timezone_from_plant_info() {
+        // No device list route, the timezone must not need it
+        let client = fixture_client(vec![
+            (
+                "panel/getPlantData",
+                r#"{"result":1,"obj":{"plantName":"Home","timezone":"2"}}"#,
+            ),
+            (
+                "panel/mix/getMIXStatusData",
+                r#"{"result":1,"obj":{"chargePower":0,"SOC":50,"pLocalLoad":0,"pPv1":0,"pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":0,"vBat":0,"vPv1":0,"lastUpdateTime":"2023-09-04 10:45:00"}}"#,
+            ),
+        ]);
+
+        let status = client.mix_status("MIX1234567", "1").await.unwrap();
+        let utc = NaiveDate::from_ymd_opt(2023, 9, 4)
+            .and_then(|d| d.and_hms_opt(8, 45, 0))
+            .unwrap()
+            .and_utc();
+        assert_eq!(i64::from(status.when), i64::from(When::from(utc)));
+    }
+
+    #[actix_rt::test]
+    async fn inbox_pages() {
+        // The third page is empty while still announcing a next one
+        let client = fixture_client(vec![
+            (
+                "getMessageList?currPage=1 ",
+                r#"{"result":1,"obj":{"datas":[{"id":"1","title":"First"}],"haveNext":true}}"#,
+            ),
+            (
+                "getMessageList?currPage=2 ",
+                r#"{"result":1,"obj":{"datas":[{"id":2,"title":"Second","isRead":"1"}],"haveNext":true}}"#,
+            ),
+            (
+                "getMessageList?currPage=3 ",
+                r#"{"result":1,"obj":{"datas":[],"haveNext":true}}"#,
+            ),
+        ]);
+
+        let messages = client.inbox().await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].id, 2);
+        assert!(messages[1].read);
+    }
+
+    #[actix_rt::test]
+    async fn plant_live_reads_mix_soc() {
+        let client = fixture_client(vec![
+            (
+                "panel/getDevicesByPlantList",
+                r#"{"result":1,"obj":{"datas":[{"sn":"MIX1234567","deviceType":"mix","pac":"1.5"},{"sn":"SPH1234567","deviceType":"sph","pac":"0.5"},{"sn":"MIX7654321","deviceType":"mix","status":"-1"}],"pages":1}}"#,
+            ),
+            (
+                "panel/mix/getMIXStatusData",
+                r#"{"result":1,"obj":{"chargePower":0,"SOC":64,"pLocalLoad":0,"pPv1":0,"pactogrid":0,"pactouser":0,"pdisCharge1":0,"vAc1":0,"vBat":0,"vPv1":0}}"#,
+            ),
+        ]);
+
+        let live = client.plant_live("1").await.unwrap();
+        assert_eq!(live.devices_total, 3);
+        assert_eq!(live.devices_online, 2);
+        assert_eq!(live.power, 2.0);
+        assert_eq!(live.soc, Some(64.0));
+    }
+
+    #[actix_rt::test]
+    async fn plant_archive_records() {
+        let mut client = fixture_client(vec![
+            (
+                "panel/getPlantData",
+                r#"{"result":1,"obj":{"plantName":"Home"}}"#,
+            ),
+            (
+                "panel/getDevicesByPlantList",
+                r#"{"result":1,"obj":{"datas":[{"sn":"MIX1234567","deviceType":"mix"}],"pages":1}}"#,
+            ),
+            (
+                "log/getNewPlantFaultLog",
+                r#"{"result":1,"obj":{"datas":[{"deviceSn":"MIX1234567","eventId":"102","time":"2023-05-02 13:05:00"}],"haveNext":false}}"#,
+            ),
+            (
+                "panel/mix/getMIXTotalData",
+                r#"{"result":1,"obj":{"epvTotal":"1234.5"}}"#,
+            ),
+            (
+                "panel/mix/getMIXEnergyDayChart",
+                r#"{"result":1,"obj":{"charts":{"ppv":[0,1]}}}"#,
+            ),
+            (
+                "device/getMIXHistory",
+                r#"{"result":1,"obj":{"datas":[],"haveNext":false}}"#,
+            ),
+        ]);
+
+        client.set_archive_pacing(Duration::ZERO);
+
+        let day = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap();
+        let mut archive = Vec::new();
+        client
+            .export_plant_archive("1", day, day, &mut archive)
+            .await
+            .unwrap();
+
+        let records: Vec<Value> = String::from_utf8(archive)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<&str> = records.iter().filter_map(|r| r["kind"].as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "plant",
+                "devices",
+                "faults",
+                "totals",
+                "day_chart",
+                "history"
+            ]
+        );
+        assert_eq!(records[2]["events"][0]["plant_id"], "1");
+        assert_eq!(records[3]["totals"]["epvTotal"], 1234.5);
+    }
 }